@@ -0,0 +1,91 @@
+//! Throughput of placing dots with `make_image`, reported in dots per second
+//!
+//! Run with `cargo bench`, and compare against a saved baseline with
+//! `cargo bench -- --save-baseline main` and `cargo bench -- --baseline main`.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use image::{Rgb, RgbImage};
+use sierpinski_triangle::{make_image, Generator, Options};
+
+/// Size of the image the dots are placed on
+const SIZE: u32 = 1000;
+
+/// The dot counts to measure
+const DOTS: [u64; 3] = [10_000, 1_000_000, 10_000_000];
+
+fn options() -> Options {
+    Options {
+        seed: Some(0),
+        ..Options::default()
+    }
+}
+
+/// Dots of a single color, as with `generate`
+fn single_color(c: &mut Criterion) {
+    let mut group = c.benchmark_group("single color");
+    group.sample_size(10);
+    for dots in DOTS {
+        group.throughput(Throughput::Elements(dots));
+        group.bench_with_input(BenchmarkId::from_parameter(dots), &dots, |b, &dots| {
+            b.iter(|| {
+                make_image(
+                    RgbImage::new(SIZE, SIZE),
+                    black_box(dots),
+                    &options(),
+                    |_, _, _| Rgb([255, 255, 255]),
+                )
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Dots of a single color placed with the fast generator, to compare with `single color`
+fn fast_generator(c: &mut Criterion) {
+    let options = Options {
+        generator: Generator::Fast,
+        ..options()
+    };
+
+    let mut group = c.benchmark_group("fast generator");
+    group.sample_size(10);
+    for dots in DOTS {
+        group.throughput(Throughput::Elements(dots));
+        group.bench_with_input(BenchmarkId::from_parameter(dots), &dots, |b, &dots| {
+            b.iter(|| {
+                make_image(
+                    RgbImage::new(SIZE, SIZE),
+                    black_box(dots),
+                    &options,
+                    |_, _, _| Rgb([255, 255, 255]),
+                )
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Dots colored by the pixel of another image, as with `image`, which reads the image all over
+fn overlay(c: &mut Criterion) {
+    let source = RgbImage::from_fn(SIZE, SIZE, |x, y| Rgb([x as u8, y as u8, (x ^ y) as u8]));
+
+    let mut group = c.benchmark_group("overlay");
+    group.sample_size(10);
+    for dots in DOTS {
+        group.throughput(Throughput::Elements(dots));
+        group.bench_with_input(BenchmarkId::from_parameter(dots), &dots, |b, &dots| {
+            b.iter(|| {
+                make_image(
+                    RgbImage::new(SIZE, SIZE),
+                    black_box(dots),
+                    &options(),
+                    |x, y, _| *source.get_pixel(x, y),
+                )
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, single_color, fast_generator, overlay);
+criterion_main!(benches);
@@ -0,0 +1,130 @@
+//! The chaos game with the dots kept at their exact position, splatted over the pixels around it
+
+use image::{ImageBuffer, Pixel, Primitive};
+use log::info;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use std::time::Instant;
+
+use crate::{
+    channel, choose, get_seed, interpolate, log_timing, prepare, progress_bar, progress_step,
+    Options,
+};
+
+/// Play the chaos game like [`make_image`](crate::make_image), but without rounding the dots to
+/// whole pixels
+///
+/// Every dot is spread over the 2x2 pixels around it with bilinear weights. Afterwards each pixel
+/// gets the weighted average color of the dots on it, covering the pixel of `image` as much as the
+/// summed weights do (Full from one whole dot on). This always runs on a single thread, and draws
+/// dots of a single pixel.
+pub fn make_antialiased<P, F>(
+    image: ImageBuffer<P, Vec<P::Subpixel>>,
+    dots: u64,
+    options: &Options,
+    color: F,
+) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel,
+    F: Fn(u32, u32, u64) -> P,
+{
+    let (width, height) = image.dimensions();
+    let positions = options.positions(width, height);
+    let mut img = prepare(image, dots, &positions, options, |_, x, y| color(x, y, 0));
+
+    let channels = P::CHANNEL_COUNT as usize;
+    let pixels = width as usize * height as usize;
+    let mut weights = vec![0f32; pixels];
+    let mut sums = vec![0f32; pixels * channels];
+    let mut splat = |x: f64, y: f64, px: P| {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let corners = [
+            (0, 0, (1.0 - tx) * (1.0 - ty)),
+            (1, 0, tx * (1.0 - ty)),
+            (0, 1, (1.0 - tx) * ty),
+            (1, 1, tx * ty),
+        ];
+        for (dx, dy, weight) in corners {
+            let (x, y) = (x0 as u32 + dx, y0 as u32 + dy);
+            if weight == 0.0 || x >= width || y >= height {
+                continue;
+            }
+
+            let index = y as usize * width as usize + x as usize;
+            weights[index] += weight as f32;
+            for (sum, &c) in sums[index * channels..].iter_mut().zip(px.channels()) {
+                *sum += weight as f32 * channel(c) as f32;
+            }
+        }
+    };
+
+    let seed = get_seed(options);
+    info!("Placing dots (Seed: {seed})");
+    let bar = progress_bar(dots, options);
+    let tick = progress_step(dots);
+    let start_time = Instant::now();
+    let mut rng = StdRng::seed_from_u64(seed);
+    let vertices: Vec<[f64; 2]> = positions
+        .iter()
+        .map(|&[x, y]| [x as f64, y as f64])
+        .collect();
+    let mut last = options
+        .start(&positions, width, height)
+        .map(|axis| axis as f64);
+    let mut prev = None;
+    let mut placed = 0;
+    let distribution = options.distribution();
+    for step in 0..options.skip.saturating_add(dots) {
+        let n = choose(
+            &mut rng,
+            prev,
+            vertices.len(),
+            options.restrict,
+            distribution.as_ref(),
+        );
+        prev = Some(n);
+        // The first dots aren't drawn, as they haven't reached the fractal yet
+        if step >= options.skip {
+            let i = step - options.skip + 1;
+            let [x, y] = last;
+            let (px, py) = (
+                (x.round() as u32).min(width - 1),
+                (y.round() as u32).min(height - 1),
+            );
+            splat(x, y, color(px, py, i));
+            placed = i;
+            if i.is_multiple_of(tick) {
+                bar.inc(tick);
+                if options.stopped() {
+                    break;
+                }
+            }
+        }
+        for (axis, target) in last.iter_mut().zip(vertices[n]) {
+            *axis += (target - *axis) * options.ratio;
+        }
+    }
+    bar.finish();
+    log_timing(placed, dots, options, start_time.elapsed());
+
+    info!("Blending the dots");
+    for (index, &weight) in weights.iter().enumerate().filter(|(_, &w)| w > 0.0) {
+        let (x, y) = (
+            (index % width as usize) as u32,
+            (index / width as usize) as u32,
+        );
+        let average: Vec<P::Subpixel> = sums[index * channels..(index + 1) * channels]
+            .iter()
+            .map(|sum| {
+                num_traits::cast((sum / weight).round()).unwrap_or(Primitive::DEFAULT_MAX_VALUE)
+            })
+            .collect();
+        let px = *P::from_slice(&average);
+        let under = *img.get_pixel(x, y);
+        img.put_pixel(x, y, interpolate(under, px, weight.min(1.0) as f64));
+    }
+
+    img
+}
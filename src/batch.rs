@@ -0,0 +1,143 @@
+//! Generating many images from a list of jobs in a TOML or JSON file
+
+use image::{DynamicImage, ImageFormat, Rgb, Rgba};
+use log::{error, info, warn};
+use serde::Deserialize;
+use sierpinski_triangle::{generate_triangle, get_color};
+
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+use std::time::Instant;
+
+use crate::{
+    can_save, default_filename, encode_webp, is_svg, validate_dimensions, validate_dots, write_svg,
+    FilenameOpts,
+};
+
+/// One image to generate, with the same meaning as the arguments of `generate`
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Job {
+    pub width: u32,
+    pub height: u32,
+    pub dots: u64,
+    pub color: Option<String>,
+    pub output: Option<String>,
+    pub seed: Option<u64>,
+}
+
+/// The jobs of a TOML file, written as `[[job]]` tables
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlBatch {
+    #[serde(default)]
+    job: Vec<Job>,
+}
+
+/// Read the jobs from `path`, as a JSON list if it ends in .json and as TOML otherwise
+pub fn read_jobs(path: &str) -> Result<Vec<Job>, String> {
+    let text = fs::read_to_string(path).map_err(|err| format!("Couldn't read {path}: {err}"))?;
+    let json = Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+
+    if json {
+        serde_json::from_str(&text).map_err(|err| format!("Couldn't parse {path}: {err}"))
+    } else {
+        toml::from_str::<TomlBatch>(&text)
+            .map(|batch| batch.job)
+            .map_err(|err| format!("Couldn't parse {path}: {err}"))
+    }
+}
+
+/// Generate every job, logging the ones that fail without stopping, and return how many failed
+pub fn run(jobs: &[Job], strict: bool, force: bool, max_memory: Option<u64>, yes: bool) -> usize {
+    let mut failed = 0;
+    for (n, job) in jobs.iter().enumerate() {
+        info!(
+            "Job {}/{}: {}x{} with {} dots",
+            n + 1,
+            jobs.len(),
+            job.width,
+            job.height,
+            job.dots
+        );
+        let job_time = Instant::now();
+        match run_job(job, strict, force, max_memory, yes) {
+            Ok(path) => info!("Saved {path} in {}ms", job_time.elapsed().as_millis()),
+            Err(err) => {
+                error!("Job {} failed: {err}", n + 1);
+                failed += 1;
+            }
+        }
+    }
+
+    failed
+}
+
+fn run_job(
+    job: &Job,
+    strict: bool,
+    force: bool,
+    max_memory: Option<u64>,
+    yes: bool,
+) -> Result<String, String> {
+    validate_dimensions(job.width, job.height, 3, max_memory)?;
+    validate_dots(job.dots, yes)?;
+
+    let color = match job.color.as_deref().map(get_color) {
+        None => Rgba([255, 255, 255, 255]),
+        Some(Ok(color)) => color,
+        Some(Err(err)) if strict => return Err(format!("Invalid color: {err}")),
+        Some(Err(err)) => {
+            warn!("{err}, using white.");
+            Rgba([255, 255, 255, 255])
+        }
+    };
+    let Rgba([r, g, b, _]) = color;
+
+    let path = match &job.output {
+        Some(path) => path.clone(),
+        None => default_filename(
+            job.width,
+            job.height,
+            job.dots,
+            &FilenameOpts {
+                extension: "png",
+                ..FilenameOpts::default()
+            },
+        ),
+    };
+    if !force && Path::new(&path).exists() {
+        return Err(format!(
+            "{path} already exists, use --force to overwrite it"
+        ));
+    }
+    let format = if is_svg(&path) {
+        None
+    } else {
+        let format = ImageFormat::from_path(&path)
+            .ok()
+            .filter(can_save)
+            .ok_or_else(|| {
+                format!("The file extension of {path} isn't a supported image format")
+            })?;
+        Some(format)
+    };
+
+    let img = generate_triangle(job.width, job.height, job.dots, Rgb([r, g, b]), job.seed);
+    match format {
+        Some(ImageFormat::WebP) => encode_webp(&DynamicImage::from(img), None)
+            .and_then(|bytes| fs::write(&path, bytes).map_err(|err| err.to_string())),
+        Some(format) => img
+            .save_with_format(&path, format)
+            .map_err(|err| err.to_string()),
+        None => File::create(&path)
+            .and_then(|file| write_svg(&DynamicImage::from(img).to_rgba8(), BufWriter::new(file)))
+            .map_err(|err| err.to_string()),
+    }
+    .map_err(|err| format!("Couldn't save to {path}: {err}"))?;
+
+    Ok(path)
+}
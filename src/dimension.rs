@@ -0,0 +1,113 @@
+//! Estimating the fractal dimension of what's drawn on an image by counting boxes
+
+use image::{ImageBuffer, Pixel};
+
+/// Smallest number of box sizes the dimension is fitted to
+const MIN_SCALES: usize = 3;
+
+/// Estimate the box-counting dimension of the pixels of `image` that `drawn` is true for
+///
+/// The image is covered with grids of square boxes of 1, 2, 4 and so on pixels, up to an eighth
+/// of its shortest side, counting the boxes with anything drawn in them. The dimension is the
+/// slope of the line fitted to the logarithm of the counts against the logarithm of one over the
+/// box size, which is about 1.585 for a Sierpiński triangle with enough dots. Returns `None` when
+/// nothing is drawn or the image is too small for enough box sizes.
+pub fn box_dimension<P, F>(image: &ImageBuffer<P, Vec<P::Subpixel>>, drawn: F) -> Option<f64>
+where
+    P: Pixel,
+    F: Fn(&P) -> bool,
+{
+    let (width, height) = image.dimensions();
+    let points: Vec<(u32, u32)> = image
+        .enumerate_pixels()
+        .filter(|(_, _, px)| drawn(px))
+        .map(|(x, y, _)| (x, y))
+        .collect();
+    if points.is_empty() {
+        return None;
+    }
+
+    let mut samples = Vec::new();
+    let mut size = 1;
+    while size <= width.min(height) / 8 {
+        // Shifting the grid by half a box can cover the same pixels with fewer boxes, and the
+        // fewest is the closest to the actual count
+        let count = [0, size / 2]
+            .into_iter()
+            .flat_map(|dx| [0, size / 2].map(|dy| (dx, dy)))
+            .map(|(dx, dy)| count_boxes(&points, width + dx, height + dy, size, dx, dy))
+            .min()
+            .unwrap_or_default();
+        samples.push(((1.0 / size as f64).ln(), (count as f64).ln()));
+        size *= 2;
+    }
+    if samples.len() < MIN_SCALES {
+        return None;
+    }
+
+    // The slope of the least squares line through the samples
+    let n = samples.len() as f64;
+    let (mean_x, mean_y) = samples
+        .iter()
+        .fold((0.0, 0.0), |(mx, my), &(x, y)| (mx + x / n, my + y / n));
+    let (covariance, variance) = samples.iter().fold((0.0, 0.0), |(c, v), &(x, y)| {
+        (c + (x - mean_x) * (y - mean_y), v + (x - mean_x).powi(2))
+    });
+
+    Some(covariance / variance)
+}
+
+/// The number of `size`x`size` boxes of a grid over `width`x`height` pixels with any of `points`
+/// in them, with the points moved right by `dx` and down by `dy`
+fn count_boxes(
+    points: &[(u32, u32)],
+    width: u32,
+    height: u32,
+    size: u32,
+    dx: u32,
+    dy: u32,
+) -> usize {
+    let columns = width.div_ceil(size) as usize;
+    let mut boxes = vec![false; columns * height.div_ceil(size) as usize];
+    for &(x, y) in points {
+        boxes[((y + dy) / size) as usize * columns + ((x + dx) / size) as usize] = true;
+    }
+
+    boxes.iter().filter(|&&hit| hit).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::{GrayImage, Luma};
+
+    /// How far the estimated dimensions may be off
+    const TOLERANCE: f64 = 0.05;
+
+    fn dimension(image: &GrayImage) -> f64 {
+        box_dimension(image, |px| px[0] > 0).unwrap()
+    }
+
+    #[test]
+    fn filled_square() {
+        let square = GrayImage::from_pixel(256, 256, Luma([255]));
+        assert!((dimension(&square) - 2.0).abs() < TOLERANCE);
+    }
+
+    #[test]
+    fn full_resolution_triangle() {
+        // The pixels where x and y share no bits make up the triangle down to single pixels
+        let triangle =
+            GrayImage::from_fn(512, 512, |x, y| Luma([if x & y == 0 { 255 } else { 0 }]));
+        assert!((dimension(&triangle) - 3f64.log2()).abs() < TOLERANCE);
+    }
+
+    #[test]
+    fn nothing_drawn() {
+        assert_eq!(
+            box_dimension(&GrayImage::new(256, 256), |px| px[0] > 0),
+            None
+        );
+    }
+}
@@ -0,0 +1,43 @@
+//! The Barnsley fern, made with the same chaos game but jumping along affine maps instead of
+//! towards vertices
+
+use image::{ImageBuffer, Pixel};
+use log::info;
+
+use crate::ifs::{play_maps, Map};
+use crate::Options;
+
+/// The maps of the fern as `[a, b, c, d, e, f]`, taking x,y to ax + by + e, cx + dy + f, with the
+/// probability of choosing them
+const MAPS: [Map; 4] = [
+    // The stem
+    ([0.0, 0.0, 0.0, 0.16, 0.0, 0.0], 0.01),
+    // Smaller and smaller copies of the fern up the stem
+    ([0.85, 0.04, -0.04, 0.85, 0.0, 1.6], 0.85),
+    // The largest leaf on the left
+    ([0.2, -0.26, 0.23, 0.22, 0.0, 1.6], 0.07),
+    // The largest leaf on the right
+    ([-0.15, 0.28, 0.26, 0.24, 0.0, 0.44], 0.07),
+];
+
+/// The smallest and largest x and y of the fern
+const BOUNDS: [[f64; 2]; 2] = [[-2.182, 2.6558], [0.0, 9.9983]];
+
+/// Play the chaos game with the maps of the Barnsley fern, placing `dots` dots colored by `color`
+/// on `image`, fitted inside the margin
+///
+/// Only the seed, skip, dot size, progress and margin of `options` are used, as the fern has no vertices.
+pub fn make_fern<P, F>(
+    image: ImageBuffer<P, Vec<P::Subpixel>>,
+    dots: u64,
+    options: &Options,
+    color: F,
+) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel,
+    F: Fn(u32, u32, u64) -> P,
+{
+    let (width, height) = image.dimensions();
+    info!("Creating a Barnsley fern with {dots} points on a {width}x{height} image");
+    play_maps(image, dots, &MAPS, BOUNDS, options, color)
+}
@@ -0,0 +1,141 @@
+//! Iterated function systems: the chaos game jumping along any affine maps instead of towards
+//! vertices, which is how the Barnsley fern is made too
+
+use image::{ImageBuffer, Pixel};
+use log::info;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use std::time::Instant;
+
+use crate::{get_seed, inset, log_timing, progress_bar, progress_step, stamp, Options};
+
+/// An affine map as `[a, b, c, d, e, f]`, taking x,y to ax + by + e, cx + dy + f, with the
+/// probability of choosing it
+pub type Map = ([f64; 6], f64);
+
+/// Number of jumps the bounds of the maps are measured over
+const BOUND_DOTS: u64 = 100_000;
+
+/// Number of jumps made before measuring the bounds, as they haven't reached the fractal yet
+const BOUND_SKIP: u64 = 100;
+
+/// Play the chaos game with `maps` like [`make_fern`](crate::make_fern), fitting the part of the
+/// plane inside `bounds` (`[[x0, x1], [y0, y1]]`, as from [`ifs_bounds`]) into the margin
+///
+/// Only the seed, skip, dot size, progress and margin of `options` are used, as there are no
+/// vertices. The probabilities of the maps should add up to 1.
+pub fn make_ifs<P, F>(
+    image: ImageBuffer<P, Vec<P::Subpixel>>,
+    dots: u64,
+    maps: &[Map],
+    bounds: [[f64; 2]; 2],
+    options: &Options,
+    color: F,
+) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel,
+    F: Fn(u32, u32, u64) -> P,
+{
+    let (width, height) = image.dimensions();
+    info!("Creating an iterated function system of {} maps with {dots} points on a {width}x{height} image", maps.len());
+    play_maps(image, dots, maps, bounds, options, color)
+}
+
+/// The smallest and largest x and y the chaos game with `maps` reaches from the origin, or `None`
+/// when it runs off to infinity
+pub fn ifs_bounds(maps: &[Map], seed: u64) -> Option<[[f64; 2]; 2]> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut last = [0.0, 0.0];
+    let mut bounds = [[f64::INFINITY, f64::NEG_INFINITY]; 2];
+    for step in 0..BOUND_SKIP + BOUND_DOTS {
+        last = jump(maps, last, &mut rng);
+        if !last.iter().all(|c| c.is_finite()) {
+            return None;
+        }
+        if step >= BOUND_SKIP {
+            for (axis, [low, high]) in bounds.iter_mut().enumerate() {
+                *low = low.min(last[axis]);
+                *high = high.max(last[axis]);
+            }
+        }
+    }
+
+    Some(bounds)
+}
+
+/// Play the chaos game with `maps`, starting at the origin and fitting `bounds` into the margin
+pub(crate) fn play_maps<P, F>(
+    image: ImageBuffer<P, Vec<P::Subpixel>>,
+    dots: u64,
+    maps: &[Map],
+    bounds: [[f64; 2]; 2],
+    options: &Options,
+    color: F,
+) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel,
+    F: Fn(u32, u32, u64) -> P,
+{
+    let (width, height) = image.dimensions();
+    info!("Creating image");
+    let mut img = image;
+
+    let [[x0, x1], [y0, y1]] = bounds;
+    let inner = |size: u32| (size - 2 * inset(size, options.margin)) as f64;
+    // A line has no width or height to fit, giving an infinite scale on that side (And a point on both)
+    let scale = (inner(width) / (x1 - x0)).min(inner(height) / (y1 - y0));
+    let scale = if scale.is_finite() { scale } else { 1.0 };
+    let project = |[x, y]: [f64; 2]| {
+        let px = width as f64 / 2.0 + (x - (x0 + x1) / 2.0) * scale;
+        let py = height as f64 / 2.0 - (y - (y0 + y1) / 2.0) * scale;
+        (
+            (px.max(0.0) as u32).min(width - 1),
+            (py.max(0.0) as u32).min(height - 1),
+        )
+    };
+
+    let seed = get_seed(options);
+    info!("Placing dots (Seed: {seed})");
+    let bar = progress_bar(dots, options);
+    let tick = progress_step(dots);
+    let start_time = Instant::now();
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut last = [0.0, 0.0];
+    let mut placed = 0;
+    for step in 0..options.skip.saturating_add(dots) {
+        // The first dots aren't drawn, as they haven't reached the fractal yet
+        if step >= options.skip {
+            let i = step - options.skip + 1;
+            let (x, y) = project(last);
+            stamp(&mut img, x, y, i, options.dot_size, &color);
+            placed = i;
+            if i.is_multiple_of(tick) {
+                bar.inc(tick);
+                if options.stopped() {
+                    break;
+                }
+            }
+        }
+
+        last = jump(maps, last, &mut rng);
+    }
+    bar.finish();
+    log_timing(placed, dots, options, start_time.elapsed());
+
+    img
+}
+
+/// Jump from `point` along one of `maps`, chosen at random by their probabilities
+fn jump(maps: &[Map], point: [f64; 2], rng: &mut StdRng) -> [f64; 2] {
+    let mut r: f64 = rng.gen();
+    let ([a, b, c, d, e, f], _) = maps
+        .iter()
+        .find(|(_, p)| {
+            r -= p;
+            r < 0.0
+        })
+        .unwrap_or(&maps[maps.len() - 1]);
+    let [x, y] = point;
+    [a * x + b * y + e, c * x + d * y + f]
+}
@@ -0,0 +1,143 @@
+//! Exploring the number of dots, color and seed of a triangle with a live preview in the terminal
+
+use console::{Key, Term};
+use image::{Pixel, Rgb, RgbImage, Rgba};
+use log::LevelFilter;
+use sierpinski_triangle::{get_color, make_image, Options};
+
+use std::io::{self, Write};
+
+use crate::{to_hex, MAX_DOTS};
+
+/// The colors cycled through after the one the preview starts with
+const COLORS: [&str; 8] = [
+    "white",
+    "red",
+    "orange",
+    "gold",
+    "lime",
+    "cyan",
+    "dodgerblue",
+    "violet",
+];
+
+/// Lines of the terminal kept for the settings and keys below the preview
+const STATUS_LINES: u16 = 2;
+
+/// The settings being explored
+#[derive(Clone, Copy, Debug)]
+pub struct Settings {
+    pub dots: u64,
+    pub color: Rgb<u8>,
+    pub seed: u64,
+}
+
+/// Show a preview of a `width`x`height` triangle, changing `settings` with the keys until `q` or
+/// Escape is pressed, and calling `save` with the settings when `s` is pressed
+///
+/// The preview is drawn with two pixels in every character, using the colors of the terminal.
+pub fn run<S>(
+    term: &Term,
+    width: u32,
+    height: u32,
+    settings: Settings,
+    mut save: S,
+) -> io::Result<()>
+where
+    S: FnMut(Settings),
+{
+    if !term.is_term() {
+        return Err(io::Error::other("The preview needs a terminal"));
+    }
+
+    let mut colors = vec![settings.color];
+    colors.extend(
+        COLORS
+            .iter()
+            .filter_map(|name| get_color(name).ok())
+            .map(|Rgba([r, g, b, _])| Rgb([r, g, b]))
+            .filter(|&col| col != settings.color),
+    );
+    let mut color = 0;
+    let mut settings = settings;
+
+    term.hide_cursor()?;
+    let result = loop {
+        settings.color = colors[color];
+        if let Err(err) = draw(term, width, height, settings) {
+            break Err(err);
+        }
+
+        match term.read_key() {
+            Ok(Key::ArrowUp) => settings.dots = settings.dots.saturating_mul(2).min(MAX_DOTS),
+            Ok(Key::ArrowDown) => settings.dots = (settings.dots / 2).max(1),
+            Ok(Key::ArrowRight) => settings.seed = settings.seed.wrapping_add(1),
+            Ok(Key::ArrowLeft) => settings.seed = settings.seed.wrapping_sub(1),
+            Ok(Key::Char('r')) => settings.seed = rand::random(),
+            Ok(Key::Char('c')) => color = (color + 1) % colors.len(),
+            Ok(Key::Char('s')) => {
+                term.clear_screen()?;
+                term.show_cursor()?;
+                save(settings);
+                term.hide_cursor()?;
+            }
+            // Ctrl-C comes through as a character while the keys are read
+            Ok(Key::Char('q' | '\u{3}') | Key::Escape) => break Ok(()),
+            Ok(_) => {}
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => break Ok(()),
+            Err(err) => break Err(err),
+        }
+    };
+
+    term.clear_screen()?;
+    term.show_cursor()?;
+    result
+}
+
+/// Draw the preview of `settings` to fill the terminal, with the settings and keys below it
+fn draw(term: &Term, width: u32, height: u32, settings: Settings) -> io::Result<()> {
+    let (rows, columns) = term.size();
+    let lines = rows.saturating_sub(STATUS_LINES).max(1);
+    // Characters are about twice as high as they're wide, so every one of them holds two pixels
+    let scale = (columns as f64 / width as f64).min(lines as f64 * 2.0 / height as f64);
+    let preview_width = ((width as f64 * scale) as u32).max(1);
+    let preview_height = ((height as f64 * scale) as u32).max(2);
+    // The same share of the pixels is covered as in the full image
+    let dots = (settings.dots as f64 * scale * scale).ceil() as u64;
+
+    let options = Options {
+        seed: Some(settings.seed),
+        ..Options::default()
+    };
+    // The logs of every preview would scroll it off the screen
+    let level = log::max_level();
+    log::set_max_level(LevelFilter::Off);
+    let img = make_image(
+        RgbImage::new(preview_width, preview_height),
+        dots,
+        &options,
+        |_, _, _| settings.color,
+    );
+    log::set_max_level(level);
+
+    let mut screen = String::new();
+    for y in (0..preview_height - 1).step_by(2) {
+        for x in 0..preview_width {
+            let Rgb([r, g, b]) = *img.get_pixel(x, y);
+            let Rgb([br, bg, bb]) = *img.get_pixel(x, y + 1);
+            screen += &format!("\x1b[38;2;{r};{g};{b}m\x1b[48;2;{br};{bg};{bb}m▀");
+        }
+        screen += "\x1b[0m\r\n";
+    }
+    screen += &format!(
+        "\r\nDots: {}  Color: {}  Seed: {}  |  Up/Down: Dots  Left/Right/r: Seed  c: Color  s: Save  q: Quit",
+        settings.dots,
+        to_hex(settings.color.to_rgba()),
+        settings.seed
+    );
+
+    term.clear_screen()?;
+    let mut out = term.clone();
+    out.write_all(screen.as_bytes())?;
+    out.flush()
+}
@@ -0,0 +1,1620 @@
+//! Generate images of the Sierpiński triangle using the [chaos game method](https://en.wikipedia.org/wiki/Sierpi%C5%84ski_triangle#Chaos_game).
+//!
+//! Progress is reported through the [`log`] facade, so nothing is printed unless the
+//! application installs a logger.
+
+use clap::ValueEnum;
+use image::{imageops, ImageBuffer, Pixel, Primitive, Rgb, RgbImage, Rgba};
+use indicatif::{ProgressBar, ProgressStyle};
+use log::{debug, info, warn};
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::rngs::{SmallRng, StdRng};
+use rand::{thread_rng, Rng, RngCore, SeedableRng};
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+
+use std::error::Error;
+use std::fmt;
+use std::io::{self, IsTerminal};
+use std::num::ParseIntError;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+mod antialias;
+mod carpet;
+mod dimension;
+mod fern;
+mod ifs;
+mod mask;
+mod noise;
+mod stats;
+mod tetrahedron;
+mod theme;
+
+pub use antialias::make_antialiased;
+pub use carpet::make_carpet;
+pub use dimension::box_dimension;
+pub use fern::make_fern;
+pub use ifs::{ifs_bounds, make_ifs, Map};
+pub use mask::Mask;
+pub use noise::value_noise;
+pub use stats::HitCounts;
+pub use tetrahedron::make_tetrahedron;
+
+/// Rules for which vertex the chaos game may jump towards, based on the previous one
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Restriction {
+    /// Any vertex can be chosen (Gives the Sierpiński triangle, but fills squares and up)
+    None,
+
+    /// The same vertex can't be chosen twice in a row (Gives a fractal of crosses for squares and a snowflake for pentagons)
+    NoRepeat,
+
+    /// The vertex right after the previous one (Clockwise) can't be chosen (Gives a pinwheel for squares and a star for pentagons)
+    NoNeighbor,
+}
+
+/// The side of the image the computed polygon points towards
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Apex {
+    /// Pointing up
+    Top,
+
+    /// Pointing down
+    Bottom,
+
+    /// Pointing left
+    Left,
+
+    /// Pointing right
+    Right,
+}
+
+/// Shapes the vertices can be marked with
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CornerShape {
+    /// A filled square
+    Dot,
+
+    /// A horizontal and a vertical line crossing on the vertex
+    Cross,
+
+    /// A filled circle
+    Circle,
+}
+
+impl Restriction {
+    /// Whether jumping towards `next` is allowed after having jumped towards `prev`
+    pub fn allows(self, prev: usize, next: usize, vertices: usize) -> bool {
+        match self {
+            Restriction::None => true,
+            Restriction::NoRepeat => next != prev,
+            Restriction::NoNeighbor => next != (prev + 1) % vertices,
+        }
+    }
+}
+
+/// The random number generator the chaos game is played with
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Generator {
+    /// ChaCha12, as in `StdRng`
+    Standard,
+
+    /// Xoshiro256++, as in `SmallRng`, which is faster but not as random, and places other dots
+    /// for the same seed
+    Fast,
+}
+
+/// Settings for the chaos game played by [`make_image`]
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// Seed for the random number generator, drawn from entropy when `None`
+    pub seed: Option<u64>,
+
+    /// Number of vertices of the polygon (At least 3)
+    pub vertices: usize,
+
+    /// Coordinates of the vertices, overriding the computed ones (Must match `vertices`)
+    pub positions: Option<Vec<[u32; 2]>>,
+
+    /// The side the computed vertices point towards
+    pub apex: Apex,
+
+    /// Restriction on which vertex can be chosen next
+    pub restrict: Restriction,
+
+    /// How likely each vertex is to be chosen relative to the others, instead of all equally (One
+    /// non-negative weight for every vertex, not all zero)
+    pub weights: Option<Vec<f64>>,
+
+    /// Number of dots to jump without drawing before the actual dots are placed
+    pub skip: u64,
+
+    /// How far each dot jumps towards the chosen vertex (Strictly between 0 and 1)
+    pub ratio: f64,
+
+    /// Width and height of each dot (In pixels)
+    pub dot_size: u32,
+
+    /// Most pixels each dot is moved by at random in either direction when it's drawn
+    pub jitter: u32,
+
+    /// The random number generator the dots are placed with
+    pub generator: Generator,
+
+    /// Number of threads to place the dots on
+    pub threads: usize,
+
+    /// Show a progress bar while placing dots
+    pub progress: bool,
+
+    /// Mark the vertices with a dot before the chaos game starts
+    pub corners: bool,
+
+    /// Width and height of the marks on the vertices (In pixels)
+    pub corner_size: u32,
+
+    /// Shape of the marks on the vertices
+    pub corner_shape: CornerShape,
+
+    /// Where the dots start jumping from, instead of the first vertex (Which is on the fractal already)
+    pub start: Option<[u32; 2]>,
+
+    /// Space between the computed vertices and the edges of the image, as a fraction of its width
+    /// and height (From 0 to 0.4)
+    pub margin: f64,
+
+    /// Stop placing dots once this is set (Like on Ctrl-C), keeping the ones placed so far
+    pub stop: Option<&'static AtomicBool>,
+
+    /// The pixels the dots may land on, with the vertex chosen again for any jump off them (The
+    /// size of the image)
+    pub mask: Option<Arc<Mask>>,
+
+    /// Count how often the dots hit each pixel into these as they're placed
+    pub hits: Option<Arc<HitCounts>>,
+
+    /// Set when the dots get stuck where every jump leaves `mask`, which stops placing them (Like
+    /// `stop`) so the caller can tell the image isn't finished
+    pub stuck: Option<Arc<AtomicBool>>,
+}
+
+impl Options {
+    /// The vertices of the polygon on a `width`x`height` image
+    ///
+    /// Given vertices off the image are moved onto its edge. As every dot lands between the
+    /// vertices and the start, this keeps all of them on the image too.
+    pub fn positions(&self, width: u32, height: u32) -> Vec<[u32; 2]> {
+        match &self.positions {
+            Some(positions) => positions
+                .iter()
+                .map(|&point| on_image(point, width, height))
+                .collect(),
+            None => self.layout(width, height),
+        }
+    }
+
+    /// The computed vertices of the polygon on a `width`x`height` image, ignoring `positions`
+    pub fn layout(&self, width: u32, height: u32) -> Vec<[u32; 2]> {
+        get_positions_toward(width, height, self.vertices, self.margin, self.apex)
+    }
+
+    /// The point the dots start jumping from on a `width`x`height` image, with the vertices at
+    /// `positions` (Moved onto the image like the vertices)
+    pub fn start(&self, positions: &[[u32; 2]], width: u32, height: u32) -> [u32; 2] {
+        on_image(self.start.unwrap_or(positions[0]), width, height)
+    }
+
+    /// The distribution the vertices are chosen from, when they're weighted
+    fn distribution(&self) -> Option<WeightedIndex<f64>> {
+        self.weights.as_ref().map(|weights| {
+            WeightedIndex::new(weights).expect("the weights are non-negative and not all zero")
+        })
+    }
+
+    /// Whether placing the dots should stop early, as `stop` or `stuck` has been set
+    pub fn stopped(&self) -> bool {
+        self.stop.is_some_and(|stop| stop.load(Ordering::Relaxed)) || self.is_stuck()
+    }
+
+    /// Whether the dots got stuck where every jump leaves the mask, as `stuck` has been set
+    pub fn is_stuck(&self) -> bool {
+        self.stuck
+            .as_ref()
+            .is_some_and(|stuck| stuck.load(Ordering::Relaxed))
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            seed: None,
+            vertices: 3,
+            positions: None,
+            apex: Apex::Top,
+            restrict: Restriction::None,
+            weights: None,
+            skip: 10,
+            ratio: 0.5,
+            dot_size: 1,
+            jitter: 0,
+            generator: Generator::Standard,
+            threads: 1,
+            progress: false,
+            corners: true,
+            corner_size: 1,
+            corner_shape: CornerShape::Dot,
+            start: None,
+            margin: 0.1,
+            stop: None,
+            mask: None,
+            hits: None,
+            stuck: None,
+        }
+    }
+}
+
+/// Generate a new Sierpiński triangle in a single color on a black background
+pub fn generate_triangle(
+    width: u32,
+    height: u32,
+    dots: u64,
+    color: Rgb<u8>,
+    seed: Option<u64>,
+) -> RgbImage {
+    let options = Options {
+        seed,
+        ..Options::default()
+    };
+
+    make_image(RgbImage::new(width, height), dots, &options, |_, _, _| {
+        color
+    })
+}
+
+/// Add a Sierpiński triangle to an existing image, coloring each dot with `color_fn`
+pub fn overlay_triangle<F>(img: RgbImage, dots: u64, color_fn: F) -> RgbImage
+where
+    F: Fn(u32, u32) -> Rgb<u8>,
+{
+    make_image(img, dots, &Options::default(), |x, y, _| color_fn(x, y))
+}
+
+/// Play the chaos game on `image`, placing `dots` dots colored by `color` at their coordinates
+/// and dot number (Starting at 1, with 0 for the corners, which are skipped if `options.corners` is off)
+///
+/// With more than one thread in `options`, the walks the dots are split into are run in parallel on
+/// that many threads and merged afterwards. They place the same dots as a single thread with the
+/// same seed, so the same pixels are hit however many threads there are.
+pub fn make_image<P, F>(
+    image: ImageBuffer<P, Vec<P::Subpixel>>,
+    dots: u64,
+    options: &Options,
+    color: F,
+) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel,
+    F: Fn(u32, u32, u64) -> P,
+{
+    if options.threads <= 1 {
+        return play(image, dots, options, color, |_, _, _, _| {});
+    }
+
+    let (width, height) = image.dimensions();
+    let positions = options.positions(width, height);
+    let mut img = prepare(image, dots, &positions, options, |_, x, y| color(x, y, 0));
+
+    let start = options.start(&positions, width, height);
+    let seed = get_seed(options);
+    let threads = options.threads;
+    info!("Placing dots on {threads} threads (Seed: {seed})");
+    let bar = progress_bar(dots, options);
+    let start_time = Instant::now();
+    let (hits, placed) = on_threads(threads, || {
+        (0..walk_count(dots))
+            .into_par_iter()
+            .fold(
+                || (Hits::new(width, height), 0),
+                |(mut hits, placed), k| {
+                    let walked = walk(
+                        &positions,
+                        start,
+                        [width, height],
+                        dots,
+                        k..k + 1,
+                        options,
+                        seed,
+                        &bar,
+                        |i, x, y, _| hits.set(x, y, i, dots),
+                    );
+                    (hits, placed + walked)
+                },
+            )
+            .reduce(
+                || (Hits::new(width, height), 0),
+                |(a, a_placed), (b, b_placed)| (a.merge(b), a_placed + b_placed),
+            )
+    });
+    bar.finish();
+    log_timing(placed, dots, options, start_time.elapsed());
+
+    for (x, y, i) in hits.iter(dots) {
+        stamp(&mut img, x, y, i, options.dot_size, &color);
+    }
+
+    img
+}
+
+/// Split `image` into a grid of `cols`x`rows` tiles and play the chaos game with `dots` dots in each
+/// of them like [`make_image`]
+///
+/// The vertices are computed for each tile, ignoring `options.positions`, `options.start` and `options.mask`. With a seed in `options`,
+/// tile k (Counting along the rows from 0) uses the seed plus k. With `flip`, every other tile is
+/// turned upside down like the squares of a checkerboard. Pixels left over to the right and bottom
+/// of the grid are kept as they are.
+pub fn make_tiles<P, F>(
+    image: ImageBuffer<P, Vec<P::Subpixel>>,
+    dots: u64,
+    options: &Options,
+    [cols, rows]: [u32; 2],
+    flip: bool,
+    color: F,
+) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel + 'static,
+    F: Fn(u32, u32, u64) -> P,
+{
+    let (width, height) = image.dimensions();
+    let (tile_width, tile_height) = (width / cols, height / rows);
+    let mut img = image;
+    for row in 0..rows {
+        for col in 0..cols {
+            let k = row * cols + col;
+            info!("Making tile {} of {}", k + 1, cols * rows);
+
+            let mut positions = options.layout(tile_width, tile_height);
+            if flip && (row + col) % 2 == 1 {
+                for [x, y] in &mut positions {
+                    *x = tile_width - 1 - *x;
+                    *y = tile_height - 1 - *y;
+                }
+            }
+            let tile_options = Options {
+                seed: options.seed.map(|seed| seed.wrapping_add(k as u64)),
+                positions: Some(positions),
+                start: None,
+                mask: None,
+                ..options.clone()
+            };
+
+            let (x, y) = (col * tile_width, row * tile_height);
+            let tile = imageops::crop_imm(&img, x, y, tile_width, tile_height).to_image();
+            let tile = make_image(tile, dots, &tile_options, |px, py, i| {
+                color(x + px, y + py, i)
+            });
+            imageops::replace(&mut img, &tile, x as i64, y as i64);
+        }
+    }
+
+    img
+}
+
+/// Play the chaos game like [`make_image`] `layers` times on the same image, each time with the
+/// vertices moved a little at random, coloring the dots of layer k by `color` with k first
+///
+/// The vertices move at most a fiftieth of the image in each direction, and `options.start` is
+/// ignored so every layer starts on its own first vertex. Layer k uses the seed plus k, like tiles.
+pub fn make_layers<P, F>(
+    image: ImageBuffer<P, Vec<P::Subpixel>>,
+    dots: u64,
+    options: &Options,
+    layers: u32,
+    color: F,
+) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel,
+    F: Fn(usize, u32, u32, u64) -> P,
+{
+    let (width, height) = image.dimensions();
+    let positions = options.positions(width, height);
+    let seed = get_seed(options);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let jitter = |rng: &mut StdRng, axis: u32, size: u32| {
+        let by = (size / 50).max(1);
+        rng.gen_range(axis.saturating_sub(by)..=(axis + by).min(size - 1))
+    };
+
+    let mut img = image;
+    for k in 0..layers {
+        info!("Making layer {} of {layers}", k + 1);
+        let positions = positions
+            .iter()
+            .map(|&[x, y]| [jitter(&mut rng, x, width), jitter(&mut rng, y, height)])
+            .collect();
+        let layer_options = Options {
+            seed: Some(seed.wrapping_add(k as u64)),
+            positions: Some(positions),
+            start: None,
+            ..options.clone()
+        };
+        img = make_image(img, dots, &layer_options, |x, y, i| {
+            color(k as usize, x, y, i)
+        });
+    }
+
+    img
+}
+
+/// Play the chaos game like [`make_image`], but count how often each pixel is hit and color the
+/// hit pixels by `color` afterwards, with their log scaled density from 0 to 1
+///
+/// This shows the density of the fractal, which is hidden by solid dots. Pixels that are never hit
+/// keep their color from `image`, and no corners are drawn as they aren't hit either.
+pub fn make_heatmap<P, F>(
+    image: ImageBuffer<P, Vec<P::Subpixel>>,
+    dots: u64,
+    options: &Options,
+    color: F,
+) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel,
+    F: Fn(f64) -> P,
+{
+    let (width, height) = image.dimensions();
+    info!("Creating a heatmap of a Sierpiński triangle with {dots} points on a {width}x{height} image");
+    let positions = options.positions(width, height);
+
+    let start = options.start(&positions, width, height);
+    let seed = get_seed(options);
+    let threads = options.threads.max(1);
+    info!("Counting dots on {threads} threads (Seed: {seed})");
+    let bar = progress_bar(dots, options);
+    let start_time = Instant::now();
+    let pixels = width as usize * height as usize;
+    let (counts, placed) = on_threads(threads, || {
+        (0..walk_count(dots))
+            .into_par_iter()
+            .fold(
+                || (vec![0u32; pixels], 0),
+                |(mut counts, placed), k| {
+                    let walked = walk(
+                        &positions,
+                        start,
+                        [width, height],
+                        dots,
+                        k..k + 1,
+                        options,
+                        seed,
+                        &bar,
+                        |_, x, y, _| {
+                            let count = &mut counts[y as usize * width as usize + x as usize];
+                            *count = count.saturating_add(1);
+                        },
+                    );
+                    (counts, placed + walked)
+                },
+            )
+            .reduce(
+                || (vec![0; pixels], 0),
+                |(mut a, a_placed), (b, b_placed)| {
+                    for (a, b) in a.iter_mut().zip(b) {
+                        *a = a.saturating_add(b);
+                    }
+                    (a, a_placed + b_placed)
+                },
+            )
+    });
+    bar.finish();
+    log_timing(placed, dots, options, start_time.elapsed());
+
+    info!("Coloring the heatmap");
+    let mut img = image;
+    let max = (counts.iter().copied().max().unwrap_or(0) as f64).ln_1p();
+    for (index, &count) in counts.iter().enumerate().filter(|(_, &count)| count != 0) {
+        let (x, y) = (
+            (index % width as usize) as u32,
+            (index / width as usize) as u32,
+        );
+        let t = (count as f64).ln_1p() / max;
+        stamp(&mut img, x, y, 0, options.dot_size, |_, _, _| color(t));
+    }
+
+    img
+}
+
+/// Play the chaos game like [`make_image`], but color every dot by `color` of the index of the
+/// vertex it last jumped towards, showing the copy of the fractal around each vertex in its own color
+///
+/// The corners get the color of their own vertex. This always runs on a single thread.
+pub fn make_palette<P, F>(
+    image: ImageBuffer<P, Vec<P::Subpixel>>,
+    dots: u64,
+    options: &Options,
+    color: F,
+) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel,
+    F: Fn(usize) -> P,
+{
+    let (width, height) = image.dimensions();
+    let positions = options.positions(width, height);
+    let mut img = prepare(image, dots, &positions, options, |n, _, _| color(n));
+
+    let seed = get_seed(options);
+    info!("Placing dots (Seed: {seed})");
+    let bar = progress_bar(dots, options);
+    let start_time = Instant::now();
+    let placed = walk(
+        &positions,
+        options.start(&positions, width, height),
+        [width, height],
+        dots,
+        0..walk_count(dots),
+        options,
+        seed,
+        &bar,
+        |i, x, y, n| stamp(&mut img, x, y, i, options.dot_size, |_, _, _| color(n)),
+    );
+    bar.finish();
+    log_timing(placed, dots, options, start_time.elapsed());
+
+    img
+}
+
+/// Play the chaos game like [`make_image`], returning `frames` snapshots of the image evenly
+/// spread over the placed dots (The last one being the finished image)
+///
+/// This always runs on a single thread, as the snapshots need the dots in order.
+pub fn make_frames<P, F>(
+    image: ImageBuffer<P, Vec<P::Subpixel>>,
+    dots: u64,
+    options: &Options,
+    frames: u32,
+    color: F,
+) -> Vec<ImageBuffer<P, Vec<P::Subpixel>>>
+where
+    P: Pixel,
+    F: Fn(u32, u32, u64) -> P,
+{
+    let mut snapshots = Vec::with_capacity(frames as usize);
+    let mut next = 1;
+    let img = play(image, dots, options, color, |i, _, _, img| {
+        while next < frames && i as u128 >= dots as u128 * next as u128 / frames as u128 {
+            snapshots.push(img.clone());
+            next += 1;
+        }
+    });
+    snapshots.push(img);
+
+    snapshots
+}
+
+/// Play the chaos game like [`make_image`] on a `width`x`height` image without drawing anything,
+/// calling `point` with the dot number and the coordinates of every dot instead
+///
+/// This always runs on a single thread, placing the same dots as an image with the same seed.
+pub fn make_points<F>(width: u32, height: u32, dots: u64, options: &Options, mut point: F)
+where
+    F: FnMut(u64, u32, u32),
+{
+    let positions = options.positions(width, height);
+    let seed = get_seed(options);
+    info!("Placing {dots} dots without an image (Seed: {seed})");
+    let bar = progress_bar(dots, options);
+    let start_time = Instant::now();
+    let placed = walk(
+        &positions,
+        options.start(&positions, width, height),
+        [width, height],
+        dots,
+        0..walk_count(dots),
+        options,
+        seed,
+        &bar,
+        |i, x, y, _| point(i, x, y),
+    );
+    bar.finish();
+    log_timing(placed, dots, options, start_time.elapsed());
+}
+
+/// Play the chaos game like [`make_image`], calling `checkpoint` with the dot number and the image
+/// every `every` dots, so the unfinished image can be saved along the way
+///
+/// This always runs on a single thread, and there's no checkpoint after the last dot as the
+/// finished image is returned instead.
+pub fn make_checkpoints<P, F, C>(
+    image: ImageBuffer<P, Vec<P::Subpixel>>,
+    dots: u64,
+    options: &Options,
+    every: u64,
+    color: F,
+    mut checkpoint: C,
+) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel,
+    F: Fn(u32, u32, u64) -> P,
+    C: FnMut(u64, &ImageBuffer<P, Vec<P::Subpixel>>),
+{
+    play(image, dots, options, color, |i, _, _, img| {
+        if i.is_multiple_of(every) && i < dots {
+            checkpoint(i, img);
+        }
+    })
+}
+
+/// Play the chaos game like [`make_image`], then cut the image down to the smallest rectangle
+/// holding all the dots and corners
+///
+/// The edges of the dots are kept track of while they're placed, so the image isn't searched
+/// afterwards. This always runs on a single thread.
+pub fn make_cropped<P, F>(
+    image: ImageBuffer<P, Vec<P::Subpixel>>,
+    dots: u64,
+    options: &Options,
+    color: F,
+) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel + 'static,
+    F: Fn(u32, u32, u64) -> P,
+{
+    let (width, height) = image.dimensions();
+    let size = options.dot_size;
+    // The left, top, right and bottom edges of what's drawn so far
+    let mut bounds = [u32::MAX, u32::MAX, 0, 0];
+    let mut extend = |x: u32, y: u32, size: u32| {
+        let [left, top, right, bottom] = &mut bounds;
+        *left = (*left).min(x.saturating_sub((size - 1) / 2));
+        *top = (*top).min(y.saturating_sub((size - 1) / 2));
+        *right = (*right).max((x + size / 2).min(width - 1));
+        *bottom = (*bottom).max((y + size / 2).min(height - 1));
+    };
+    if options.corners {
+        for [x, y] in options.positions(width, height) {
+            extend(x, y, options.corner_size);
+        }
+    }
+
+    let img = play(image, dots, options, color, |_, x, y, _| extend(x, y, size));
+    let [left, top, right, bottom] = bounds;
+    if left > right {
+        // Nothing was drawn, so there's nothing to crop to
+        return img;
+    }
+
+    info!(
+        "Cropping the image to {}x{}",
+        right - left + 1,
+        bottom - top + 1
+    );
+    imageops::crop_imm(&img, left, top, right - left + 1, bottom - top + 1).to_image()
+}
+
+/// The single threaded chaos game, calling `step` with the dot number, its coordinates and the
+/// image after each placed dot
+fn play<P, F, S>(
+    image: ImageBuffer<P, Vec<P::Subpixel>>,
+    dots: u64,
+    options: &Options,
+    color: F,
+    mut step: S,
+) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel,
+    F: Fn(u32, u32, u64) -> P,
+    S: FnMut(u64, u32, u32, &ImageBuffer<P, Vec<P::Subpixel>>),
+{
+    let (width, height) = image.dimensions();
+    let positions = options.positions(width, height);
+    let mut img = prepare(image, dots, &positions, options, |_, x, y| color(x, y, 0));
+
+    let seed = get_seed(options);
+    info!("Placing dots (Seed: {seed})");
+    let bar = progress_bar(dots, options);
+    let start_time = Instant::now();
+    let placed = walk(
+        &positions,
+        options.start(&positions, width, height),
+        [width, height],
+        dots,
+        0..walk_count(dots),
+        options,
+        seed,
+        &bar,
+        |i, x, y, _| {
+            stamp(&mut img, x, y, i, options.dot_size, &color);
+            step(i, x, y, &img);
+        },
+    );
+    bar.finish();
+    log_timing(placed, dots, options, start_time.elapsed());
+
+    img
+}
+
+/// Log what is about to be made and mark the vertices on the image (Unless `options.corners` is
+/// off), coloring their pixels by `color` with the vertex number and coordinates
+fn prepare<P, F>(
+    image: ImageBuffer<P, Vec<P::Subpixel>>,
+    dots: u64,
+    positions: &[[u32; 2]],
+    options: &Options,
+    color: F,
+) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel,
+    F: Fn(usize, u32, u32) -> P,
+{
+    let (width, height) = image.dimensions();
+    info!("Creating a Sierpiński triangle with {dots} points on a {width}x{height} image");
+
+    info!("Creating image");
+    let mut img = image;
+
+    if options.corners {
+        info!("Placing corners");
+        for (n, &[x, y]) in positions.iter().enumerate() {
+            mark(&mut img, x, y, options, |x, y| color(n, x, y));
+        }
+    }
+
+    img
+}
+
+/// Draw the mark of a vertex at `x`,`y` in the size and shape of `options`, clamped to the image
+fn mark<P, F>(
+    img: &mut ImageBuffer<P, Vec<P::Subpixel>>,
+    x: u32,
+    y: u32,
+    options: &Options,
+    color: F,
+) where
+    P: Pixel,
+    F: Fn(u32, u32) -> P,
+{
+    let size = options.corner_size.max(1);
+    let (width, height) = img.dimensions();
+    let (x0, y0) = (
+        x.saturating_sub((size - 1) / 2),
+        y.saturating_sub((size - 1) / 2),
+    );
+    let (x1, y1) = (
+        (x + size / 2).min(width - 1),
+        (y + size / 2).min(height - 1),
+    );
+    // The middle of the square the mark fills, which is between pixels for even sizes
+    let offset = if size.is_multiple_of(2) { 0.5 } else { 0.0 };
+    let (cx, cy) = (x as f64 + offset, y as f64 + offset);
+    let radius = size as f64 / 2.0;
+    for py in y0..=y1 {
+        for px in x0..=x1 {
+            let inside = match options.corner_shape {
+                CornerShape::Dot => true,
+                CornerShape::Cross => px == x || py == y,
+                CornerShape::Circle => {
+                    (px as f64 - cx).powi(2) + (py as f64 - cy).powi(2) <= radius.powi(2)
+                }
+            };
+            if inside {
+                img.put_pixel(px, py, color(px, py));
+            }
+        }
+    }
+}
+
+/// Draw dot `i` as a `size`x`size` square of pixels around `x`,`y`, clamped to the image
+fn stamp<P, F>(
+    img: &mut ImageBuffer<P, Vec<P::Subpixel>>,
+    x: u32,
+    y: u32,
+    i: u64,
+    size: u32,
+    color: F,
+) where
+    P: Pixel,
+    F: Fn(u32, u32, u64) -> P,
+{
+    if size == 1 {
+        img.put_pixel(x, y, color(x, y, i));
+        return;
+    }
+
+    let (width, height) = img.dimensions();
+    let (x0, y0) = (
+        x.saturating_sub((size - 1) / 2),
+        y.saturating_sub((size - 1) / 2),
+    );
+    let (x1, y1) = (
+        (x + size / 2).min(width - 1),
+        (y + size / 2).min(height - 1),
+    );
+    for py in y0..=y1 {
+        for px in x0..=x1 {
+            img.put_pixel(px, py, color(px, py, i));
+        }
+    }
+}
+
+fn get_seed(options: &Options) -> u64 {
+    // Draw a seed from entropy when none is given, so it can still be logged and reused
+    options.seed.unwrap_or_else(|| thread_rng().gen())
+}
+
+/// Run `work` with its parallel iterators spread over `threads` threads
+fn on_threads<R, W>(threads: usize, work: W) -> R
+where
+    R: Send,
+    W: FnOnce() -> R + Send,
+{
+    match ThreadPoolBuilder::new().num_threads(threads).build() {
+        Ok(pool) => pool.install(work),
+        Err(err) => {
+            warn!("Couldn't start {threads} threads, using as many as there are cores: {err}");
+            work()
+        }
+    }
+}
+
+fn progress_bar(dots: u64, options: &Options) -> ProgressBar {
+    if !options.progress {
+        return ProgressBar::hidden();
+    }
+
+    let bar = ProgressBar::new(dots).with_style(ProgressStyle::default_bar().template(
+        "{percent:>3}% [{wide_bar}] {pos}/{len} dots ({per_sec}), {elapsed_precise} elapsed, ETA {eta}",
+    ));
+    // The bar is only drawn on terminals, so anywhere else (Like CI logs) the progress is logged
+    if !io::stderr().is_terminal() {
+        log_progress(&bar);
+    }
+
+    bar
+}
+
+/// How often the progress is logged when the progress bar can't be drawn
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Log how far along `bar` is every [`PROGRESS_INTERVAL`] until it's finished or dropped
+fn log_progress(bar: &ProgressBar) {
+    let bar = bar.downgrade();
+    thread::spawn(move || loop {
+        thread::sleep(PROGRESS_INTERVAL);
+        match bar.upgrade() {
+            Some(bar) if !bar.is_finished() => info!(
+                "Placed {} of {} dots ({}%)",
+                bar.position(),
+                bar.length(),
+                bar.position() * 100 / bar.length().max(1)
+            ),
+            _ => break,
+        }
+    });
+}
+
+/// How many of `dots` dots to place between updates of the progress bar, so it moves every percent
+fn progress_step(dots: u64) -> u64 {
+    (dots / 100).max(1)
+}
+
+/// Log how fast the `placed` dots were placed, warning when that's fewer than the `dots` asked for
+/// as they were interrupted
+fn log_timing(placed: u64, dots: u64, options: &Options, elapsed: Duration) {
+    if placed < dots && !options.is_stuck() {
+        warn!("Interrupted after placing {placed} of {dots} dots");
+    }
+
+    let rate = placed as f64 / elapsed.as_secs_f64().max(1e-9);
+    let rate = if rate >= 1e9 {
+        format!("{:.1}G", rate / 1e9)
+    } else if rate >= 1e6 {
+        format!("{:.1}M", rate / 1e6)
+    } else if rate >= 1e3 {
+        format!("{:.1}k", rate / 1e3)
+    } else {
+        format!("{rate:.0}")
+    };
+
+    info!(
+        "Placed {placed} dots in {}ms ({rate} dots/s)",
+        elapsed.as_millis()
+    );
+}
+
+/// Dots in each of the walks the chaos game is split into, so the same seed places the same dots
+/// however many threads the walks are spread over
+const WALK_DOTS: u64 = 1 << 16;
+
+/// The number of walks `dots` dots are split into
+fn walk_count(dots: u64) -> u64 {
+    dots.div_ceil(WALK_DOTS)
+}
+
+/// Walk the chaos game between `positions` for each of `walks` out of the [`WALK_DOTS`] dots long
+/// walks `dots` dots are split into, calling `plot` with the dot number, the coordinates of each dot
+/// and the vertex it last jumped towards (The first vertex for the start)
+///
+/// With `options.jitter`, the dots are moved a little at random before they're plotted, but kept on
+/// an image of `size`. The walk goes on from where they were before they were moved.
+///
+/// Walk k starts over from `start` with the seed `seed ^ k`, and its dots are numbered on from the
+/// walks before it, so the dots are the same whether the walks are walked one after another or on
+/// threads of their own. Returns the number of dots placed, which is less than asked for when the
+/// walks are stopped early.
+#[allow(clippy::too_many_arguments)]
+fn walk<P>(
+    positions: &[[u32; 2]],
+    start: [u32; 2],
+    size: [u32; 2],
+    dots: u64,
+    walks: Range<u64>,
+    options: &Options,
+    seed: u64,
+    bar: &ProgressBar,
+    mut plot: P,
+) -> u64
+where
+    P: FnMut(u64, u32, u32, usize),
+{
+    let ratio = options.ratio;
+    let tick = progress_step(dots);
+    let distribution = options.distribution();
+    let mut placed = 0;
+    'walks: for k in walks {
+        let first = k * WALK_DOTS;
+        let mut rng = WalkRng::new(options.generator, seed ^ k);
+        let mut last = start;
+        let mut prev = None;
+        let mut towards = 0;
+        // The skip can be as large as fits in a u64, so adding the dots could overflow
+        for step in 0..options.skip.saturating_add(WALK_DOTS.min(dots - first)) {
+            // The first dots aren't drawn, as they haven't reached the fractal yet
+            if step >= options.skip {
+                let i = first + step - options.skip + 1;
+                let [x, y] = match options.jitter {
+                    0 => last,
+                    jitter => shake(&mut rng, last, jitter, size),
+                };
+                plot(i, x, y, towards);
+                if let Some(hits) = &options.hits {
+                    hits.hit(x, y);
+                }
+                placed += 1;
+                if i.is_multiple_of(tick) {
+                    bar.inc(tick);
+                    if options.stopped() {
+                        break 'walks;
+                    }
+                }
+            }
+
+            let mut rejected = 0;
+            let chosen = loop {
+                let n = choose(
+                    &mut rng,
+                    prev,
+                    positions.len(),
+                    options.restrict,
+                    distribution.as_ref(),
+                );
+                // Keep the exact integer midpoint for the default ratio, so images don't shift by a pixel
+                let next = if ratio == 0.5 {
+                    [
+                        ((last[0] + positions[n][0]) / 2),
+                        ((last[1] + positions[n][1]) / 2),
+                    ]
+                } else {
+                    [
+                        jump(last[0], positions[n][0], ratio),
+                        jump(last[1], positions[n][1], ratio),
+                    ]
+                };
+                match &options.mask {
+                    Some(mask) if !mask.allows(next[0], next[1]) => {
+                        rejected += 1;
+                        if rejected == MAX_REJECTIONS {
+                            break None;
+                        }
+                    }
+                    _ => break Some((n, next)),
+                }
+            };
+            let Some((n, next)) = chosen else {
+                warn!("None of the vertices could be jumped towards without leaving the mask from {},{}, stopping after {placed} dots", last[0], last[1]);
+                if let Some(stuck) = &options.stuck {
+                    stuck.store(true, Ordering::Relaxed);
+                }
+                break 'walks;
+            };
+            prev = Some(n);
+            last = next;
+            towards = n;
+        }
+    }
+
+    placed
+}
+
+/// The generator of a walk, of either kind, so the walk doesn't have to be written twice
+// Only one is made for every walk, so the size of the largest one doesn't matter
+#[allow(clippy::large_enum_variant)]
+enum WalkRng {
+    Standard(StdRng),
+    Fast(SmallRng),
+}
+
+impl WalkRng {
+    fn new(generator: Generator, seed: u64) -> Self {
+        match generator {
+            Generator::Standard => WalkRng::Standard(StdRng::seed_from_u64(seed)),
+            Generator::Fast => WalkRng::Fast(SmallRng::seed_from_u64(seed)),
+        }
+    }
+}
+
+impl RngCore for WalkRng {
+    fn next_u32(&mut self) -> u32 {
+        match self {
+            WalkRng::Standard(rng) => rng.next_u32(),
+            WalkRng::Fast(rng) => rng.next_u32(),
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        match self {
+            WalkRng::Standard(rng) => rng.next_u64(),
+            WalkRng::Fast(rng) => rng.next_u64(),
+        }
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        match self {
+            WalkRng::Standard(rng) => rng.fill_bytes(dest),
+            WalkRng::Fast(rng) => rng.fill_bytes(dest),
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        match self {
+            WalkRng::Standard(rng) => rng.try_fill_bytes(dest),
+            WalkRng::Fast(rng) => rng.try_fill_bytes(dest),
+        }
+    }
+}
+
+/// Move `point` by up to `jitter` pixels in each direction at random, keeping it on an image of
+/// `size`
+fn shake<R: Rng>(rng: &mut R, point: [u32; 2], jitter: u32, size: [u32; 2]) -> [u32; 2] {
+    let jitter = i64::from(jitter);
+    [0, 1].map(|axis| {
+        let offset = rng.gen_range(-jitter..=jitter);
+        (i64::from(point[axis]) + offset).clamp(0, i64::from(size[axis]) - 1) as u32
+    })
+}
+
+/// Most times in a row the vertex is chosen again for jumps off the mask, before the dots get
+/// stuck where every jump leaves it
+const MAX_REJECTIONS: u32 = 1000;
+
+/// Pick one of `count` vertices at random, re-rolling until `restrict` allows it after `prev`
+///
+/// The vertices are equally likely, unless they're drawn from `weights`.
+fn choose<R: Rng>(
+    rng: &mut R,
+    prev: Option<usize>,
+    count: usize,
+    restrict: Restriction,
+    weights: Option<&WeightedIndex<f64>>,
+) -> usize {
+    let mut roll = || match weights {
+        Some(weights) => weights.sample(rng),
+        None => rng.gen_range(0..count),
+    };
+    let mut n = roll();
+    if let Some(p) = prev {
+        while !restrict.allows(p, n, count) {
+            n = roll();
+        }
+    }
+
+    n
+}
+
+/// The pixels hit by a walk, storing how far into the walk each of them was last hit
+///
+/// The progress is stored in 16 bits per pixel (0 meaning never hit), which is plenty for
+/// coloring by dot number while staying much smaller than a copy of the image.
+struct Hits {
+    width: u32,
+    progress: Vec<u16>,
+}
+
+impl Hits {
+    const STEPS: u128 = u16::MAX as u128 - 1;
+
+    fn new(width: u32, height: u32) -> Self {
+        Hits {
+            width,
+            progress: vec![0; width as usize * height as usize],
+        }
+    }
+
+    /// Mark `x`,`y` as hit by dot `i` out of `dots`
+    fn set(&mut self, x: u32, y: u32, i: u64, dots: u64) {
+        let index = y as usize * self.width as usize + x as usize;
+        self.progress[index] = (1 + i as u128 * Self::STEPS / dots as u128) as u16;
+    }
+
+    fn merge(mut self, other: Hits) -> Self {
+        for (a, b) in self.progress.iter_mut().zip(other.progress) {
+            *a = (*a).max(b);
+        }
+
+        self
+    }
+
+    /// The hit pixels, with the dot number out of `dots` that last hit them
+    fn iter(&self, dots: u64) -> impl Iterator<Item = (u32, u32, u64)> + '_ {
+        let width = self.width as usize;
+        self.progress
+            .iter()
+            .enumerate()
+            .filter(|(_, &progress)| progress != 0)
+            .map(move |(index, &progress)| {
+                // Dot 0 is the corners, so the first dots are rounded up to 1
+                let i = ((progress - 1) as u128 * dots as u128 / Self::STEPS).max(1);
+                ((index % width) as u32, (index / width) as u32, i as u64)
+            })
+    }
+}
+
+/// Clamp `point` to a `width`x`height` image, logging when it was off the image
+fn on_image([x, y]: [u32; 2], width: u32, height: u32) -> [u32; 2] {
+    let clamped = [x.min(width - 1), y.min(height - 1)];
+    if clamped != [x, y] {
+        debug!("Moved {x},{y} onto the edge of the {width}x{height} image");
+    }
+
+    clamped
+}
+
+fn jump(from: u32, to: u32, ratio: f64) -> u32 {
+    (from as f64 + (to as f64 - from as f64) * ratio).round() as u32
+}
+
+/// Linearly interpolate between two colors, with `t` going from 0 to 1
+pub fn interpolate<P>(from: P, to: P, t: f64) -> P
+where
+    P: Pixel,
+{
+    let t = t.clamp(0.0, 1.0);
+    from.map2(&to, |a, b| {
+        let (a, b) = (channel(a), channel(b));
+        num_traits::cast((a + (b - a) * t).round()).unwrap_or(Primitive::DEFAULT_MAX_VALUE)
+    })
+}
+
+/// Convert a color from HSV to RGB, with the hue in degrees and the saturation and value from 0 to 1
+pub fn hsv_to_rgb(hue: f64, saturation: f64, value: f64) -> Rgba<u8> {
+    let hue = hue.rem_euclid(360.0) / 60.0;
+    let (saturation, value) = (saturation.clamp(0.0, 1.0), value.clamp(0.0, 1.0));
+    let chroma = value * saturation;
+    let second = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u32 {
+        0 => (chroma, second, 0.0),
+        1 => (second, chroma, 0.0),
+        2 => (0.0, chroma, second),
+        3 => (0.0, second, chroma),
+        4 => (second, 0.0, chroma),
+        _ => (chroma, 0.0, second),
+    };
+    let byte = |c: f64| ((c + value - chroma) * 255.0).round() as u8;
+    Rgba([byte(r), byte(g), byte(b), 255])
+}
+
+/// Scale an 8 bit color channel up to 16 bits, so 255 becomes 65535
+pub fn widen(channel: u8) -> u16 {
+    channel as u16 * 257
+}
+
+/// Multiply the color channel `c` by `by`, rounding it back to its own type
+fn scale_channel<T: Primitive>(c: T, by: f64) -> T {
+    num_traits::cast((channel(c) * by).round()).unwrap_or(c)
+}
+
+/// The value of the color channel `c` as a float, for doing math with channels of any type
+fn channel<T: Primitive>(c: T) -> f64 {
+    c.to_f64().unwrap_or(0.0)
+}
+
+/// Color scales for the heatmaps made by [`make_heatmap`]
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Colormap {
+    /// From dark purple through blue and green to yellow
+    Viridis,
+
+    /// From black through purple, red and orange to pale yellow
+    Inferno,
+
+    /// From dark gray to white
+    Gray,
+}
+
+impl Colormap {
+    /// The color at `t` along the scale, from 0 to 1
+    pub fn color(self, t: f64) -> Rgb<u8> {
+        self.color_as(t, |c| c)
+    }
+
+    /// The color at `t` along the scale like [`Colormap::color`], with 16 bits per channel so the
+    /// scale is smoother
+    pub fn color16(self, t: f64) -> Rgb<u16> {
+        self.color_as(t, widen)
+    }
+
+    fn color_as<T>(self, t: f64, channel: fn(u8) -> T) -> Rgb<T>
+    where
+        Rgb<T>: Pixel,
+    {
+        let stops: &[[u8; 3]] = match self {
+            Colormap::Viridis => &[
+                [68, 1, 84],
+                [72, 40, 120],
+                [62, 73, 137],
+                [49, 104, 142],
+                [38, 130, 142],
+                [31, 158, 137],
+                [53, 183, 121],
+                [110, 206, 88],
+                [253, 231, 37],
+            ],
+            Colormap::Inferno => &[
+                [0, 0, 4],
+                [27, 12, 65],
+                [74, 12, 107],
+                [120, 28, 109],
+                [165, 44, 96],
+                [207, 68, 70],
+                [237, 105, 37],
+                [251, 155, 6],
+                [252, 255, 164],
+            ],
+            Colormap::Gray => &[[32, 32, 32], [255, 255, 255]],
+        };
+
+        let t = t.clamp(0.0, 1.0) * (stops.len() - 1) as f64;
+        let k = (t as usize).min(stops.len() - 2);
+        interpolate(
+            Rgb(stops[k].map(channel)),
+            Rgb(stops[k + 1].map(channel)),
+            t - k as f64,
+        )
+    }
+}
+
+/// Get the vertices of the polygon the chaos game is played on, `margin` (A fraction of the width
+/// and height) away from the edges
+pub fn get_positions(width: u32, height: u32, vertices: usize, margin: f64) -> Vec<[u32; 2]> {
+    get_positions_toward(width, height, vertices, margin, Apex::Top)
+}
+
+/// Like [`get_positions`], but pointing towards `apex`, as if turned a quarter or half of the way
+/// around the image while still filling the same margin
+pub fn get_positions_toward(
+    width: u32,
+    height: u32,
+    vertices: usize,
+    margin: f64,
+    apex: Apex,
+) -> Vec<[u32; 2]> {
+    let (left, top) = (inset(width, margin), inset(height, margin));
+    // Images under 10 pixels (Or without a margin) have nothing to inset, so keep the far edges inside the image
+    let (right, bottom) = (
+        (width - left).min(width - 1),
+        (height - top).min(height - 1),
+    );
+    if vertices == 3 {
+        return match apex {
+            Apex::Top => vec![[left, bottom], [right, bottom], [width / 2, top]],
+            Apex::Bottom => vec![[right, top], [left, top], [width / 2, bottom]],
+            Apex::Left => vec![[right, bottom], [right, top], [left, height / 2]],
+            Apex::Right => vec![[left, top], [left, bottom], [right, height / 2]],
+        };
+    }
+
+    // Place the vertices evenly around the ellipse inside the margin, starting at the apex
+    let (cx, cy) = (width as f64 / 2.0, height as f64 / 2.0);
+    let (rx, ry) = (cx - left as f64, cy - top as f64);
+    let first = match apex {
+        Apex::Top => -std::f64::consts::FRAC_PI_2,
+        Apex::Bottom => std::f64::consts::FRAC_PI_2,
+        Apex::Left => std::f64::consts::PI,
+        Apex::Right => 0.0,
+    };
+    (0..vertices)
+        .map(|k| {
+            let angle = first + 2.0 * std::f64::consts::PI * k as f64 / vertices as f64;
+            [
+                ((cx + rx * angle.cos()).round() as u32).min(right),
+                ((cy + ry * angle.sin()).round() as u32).min(bottom),
+            ]
+        })
+        .collect()
+}
+
+/// The pixels in a margin of `margin` times `size`
+fn inset(size: u32, margin: f64) -> u32 {
+    (size as f64 * margin) as u32
+}
+
+/// Turn `positions` by `degrees` clockwise around the center of a `width`x`height` image, keeping
+/// them inside the image
+pub fn rotate_positions(
+    positions: &[[u32; 2]],
+    degrees: f64,
+    width: u32,
+    height: u32,
+) -> Vec<[u32; 2]> {
+    let (cx, cy) = (width as f64 / 2.0, height as f64 / 2.0);
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    positions
+        .iter()
+        .map(|&[x, y]| {
+            let (dx, dy) = (x as f64 - cx, y as f64 - cy);
+            [
+                (cx + dx * cos - dy * sin)
+                    .round()
+                    .clamp(0.0, (width - 1) as f64) as u32,
+                (cy + dx * sin + dy * cos)
+                    .round()
+                    .clamp(0.0, (height - 1) as f64) as u32,
+            ]
+        })
+        .collect()
+}
+
+/// Standard CSS color names: The 16 basic HTML colors and some common extras
+const COLOR_NAMES: &[(&str, [u8; 3])] = &[
+    ("black", [0, 0, 0]),
+    ("silver", [192, 192, 192]),
+    ("gray", [128, 128, 128]),
+    ("grey", [128, 128, 128]),
+    ("white", [255, 255, 255]),
+    ("maroon", [128, 0, 0]),
+    ("red", [255, 0, 0]),
+    ("purple", [128, 0, 128]),
+    ("fuchsia", [255, 0, 255]),
+    ("magenta", [255, 0, 255]),
+    ("green", [0, 128, 0]),
+    ("lime", [0, 255, 0]),
+    ("olive", [128, 128, 0]),
+    ("yellow", [255, 255, 0]),
+    ("navy", [0, 0, 128]),
+    ("blue", [0, 0, 255]),
+    ("teal", [0, 128, 128]),
+    ("aqua", [0, 255, 255]),
+    ("cyan", [0, 255, 255]),
+    ("orange", [255, 165, 0]),
+    ("gold", [255, 215, 0]),
+    ("pink", [255, 192, 203]),
+    ("hotpink", [255, 105, 180]),
+    ("crimson", [220, 20, 60]),
+    ("coral", [255, 127, 80]),
+    ("tomato", [255, 99, 71]),
+    ("brown", [165, 42, 42]),
+    ("chocolate", [210, 105, 30]),
+    ("indigo", [75, 0, 130]),
+    ("violet", [238, 130, 238]),
+    ("orchid", [218, 112, 214]),
+    ("turquoise", [64, 224, 208]),
+    ("skyblue", [135, 206, 235]),
+    ("dodgerblue", [30, 144, 255]),
+    ("royalblue", [65, 105, 225]),
+    ("steelblue", [70, 130, 180]),
+    ("forestgreen", [34, 139, 34]),
+    ("seagreen", [46, 139, 87]),
+    ("limegreen", [50, 205, 50]),
+    ("khaki", [240, 230, 140]),
+    ("beige", [245, 245, 220]),
+    ("ivory", [255, 255, 240]),
+    ("lavender", [230, 230, 250]),
+    ("salmon", [250, 128, 114]),
+    ("tan", [210, 180, 140]),
+];
+
+/// Reasons a color couldn't be parsed by [`get_color`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorError {
+    /// No color was given
+    Empty,
+
+    /// The hex code didn't have 3, 4, 6 or 8 digits
+    BadLength(usize),
+
+    /// The hex code contained something other than hex digits
+    InvalidDigit,
+
+    /// `auto` was given, but neither the desktop nor the terminal told their color
+    NoTheme,
+}
+
+impl fmt::Display for ColorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ColorError::Empty => write!(f, "No hex color provided"),
+            ColorError::BadLength(0) => write!(f, "Expected 3, 4, 6 or 8 hex digits, got none"),
+            ColorError::BadLength(len) => {
+                write!(f, "Expected 3, 4, 6 or 8 hex digits, got {len}")
+            }
+            ColorError::InvalidDigit => {
+                write!(f, "There was an illegal character in the color code")
+            }
+            ColorError::NoTheme => write!(f, "Couldn't find the color of the theme"),
+        }
+    }
+}
+
+impl Error for ColorError {}
+
+/// Parse a CSS color name or a hex color code (With or without `#`, shorthand and alpha allowed)
+///
+/// `auto` is the accent color of the desktop, or the text color of the terminal when the desktop
+/// doesn't have one.
+pub fn get_color(hex: &str) -> Result<Rgba<u8>, ColorError> {
+    if hex.is_empty() {
+        return Err(ColorError::Empty);
+    }
+
+    if hex.trim().eq_ignore_ascii_case("auto") {
+        return theme::theme_color().ok_or(ColorError::NoTheme);
+    }
+
+    if let Some(&(_, rgb)) = COLOR_NAMES
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(hex.trim()))
+    {
+        return Ok(Rgb(rgb).to_rgba());
+    }
+
+    // Remove # from hex code
+    let hex_code = hex.strip_prefix('#').unwrap_or(hex);
+
+    if ![3, 4, 6, 8].contains(&hex_code.len()) {
+        return Err(ColorError::BadLength(hex_code.len()));
+    }
+
+    // Checking the digits up front also keeps the slicing below on char boundaries
+    if !hex_code.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(ColorError::InvalidDigit);
+    }
+
+    // Convert shorthand hex code to normal hex code (https://en.wikipedia.org/wiki/Web_colors#Shorthand_hexadecimal_form)
+    let hex_code = if hex_code.len() <= 4 {
+        let mut tmp = String::new();
+        for c in hex_code.chars() {
+            for _ in 0..2 {
+                tmp.push(c);
+            }
+        }
+
+        tmp
+    } else {
+        hex_code.to_string()
+    };
+
+    let vec = (0..hex_code.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex_code[i..i + 2], 16))
+        .collect::<Result<Vec<u8>, ParseIntError>>()
+        .map_err(|_| ColorError::InvalidDigit)?;
+
+    Ok(Rgba([
+        vec[0],
+        vec[1],
+        vec[2],
+        vec.get(3).copied().unwrap_or(255),
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::{GrayImage, Luma};
+
+    const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+    fn color(hex: &str) -> Rgba<u8> {
+        get_color(hex).unwrap()
+    }
+
+    #[test]
+    fn shorthand_with_hash() {
+        assert_eq!(color("#fff"), WHITE);
+        assert_eq!(color("#f80"), Rgba([255, 136, 0, 255]));
+    }
+
+    #[test]
+    fn full_hex_code() {
+        assert_eq!(color("ff0000"), Rgba([255, 0, 0, 255]));
+        assert_eq!(color("#1e90ff"), Rgba([30, 144, 255, 255]));
+    }
+
+    #[test]
+    fn alpha() {
+        assert_eq!(color("#f808"), Rgba([255, 136, 0, 136]));
+        assert_eq!(color("ff000080"), Rgba([255, 0, 0, 128]));
+    }
+
+    #[test]
+    fn case_insensitive() {
+        assert_eq!(color("AbCdEf"), color("abcdef"));
+        assert_eq!(color("#ABC"), color("#abc"));
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(get_color(""), Err(ColorError::Empty));
+    }
+
+    #[test]
+    fn wrong_length() {
+        assert_eq!(get_color("12345"), Err(ColorError::BadLength(5)));
+        assert_eq!(
+            ColorError::BadLength(5).to_string(),
+            "Expected 3, 4, 6 or 8 hex digits, got 5"
+        );
+    }
+
+    #[test]
+    fn only_hash() {
+        assert_eq!(get_color("#"), Err(ColorError::BadLength(0)));
+        assert_eq!(
+            ColorError::BadLength(0).to_string(),
+            "Expected 3, 4, 6 or 8 hex digits, got none"
+        );
+    }
+
+    #[test]
+    fn invalid_digit() {
+        assert_eq!(get_color("gggggg"), Err(ColorError::InvalidDigit));
+        assert_eq!(get_color("+f+f+f"), Err(ColorError::InvalidDigit));
+        assert_eq!(get_color("aéaé"), Err(ColorError::InvalidDigit));
+    }
+
+    #[test]
+    fn same_pixels_on_any_number_of_threads() {
+        let image = |threads| {
+            let options = Options {
+                seed: Some(7),
+                threads,
+                ..Options::default()
+            };
+            make_image(RgbImage::new(64, 64), 200_000, &options, |_, _, _| {
+                Rgb([255, 255, 255])
+            })
+        };
+        let single = image(1);
+        assert!(image(2) == single);
+        assert!(image(4) == single);
+    }
+
+    #[test]
+    fn stuck_on_mask() {
+        let mut mask = GrayImage::new(64, 64);
+        mask.put_pixel(5, 5, Luma([255]));
+        let options = Options {
+            seed: Some(7),
+            corners: false,
+            mask: Some(Arc::new(Mask::from_image(&mask))),
+            stuck: Some(Arc::default()),
+            ..Options::default()
+        };
+        make_image(RgbImage::new(64, 64), 1000, &options, |_, _, _| {
+            Rgb([255, 255, 255])
+        });
+
+        assert!(options.is_stuck());
+    }
+}
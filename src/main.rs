@@ -1,27 +1,693 @@
-use clap::{Parser, Subcommand};
+use arboard::{Clipboard, ImageData};
+use chrono::{Local, NaiveDateTime};
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use console::Term;
+use display_info::DisplayInfo;
 use env_logger::Builder;
-use image::{GenericImageView, ImageBuffer, Rgb, RgbImage};
-use indicatif::ProgressBar;
-use log::{error, info, warn, LevelFilter};
-use rand::{thread_rng, Rng};
-
-use std::fs;
-use std::io::Write;
-use std::num::IntErrorKind;
-use std::num::ParseIntError;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::{self, FilterType};
+use image::{
+    ColorType, Delay, DynamicImage, Frame, GenericImage, GenericImageView, ImageBuffer, ImageError,
+    ImageFormat, ImageResult, Pixel, Rgb, RgbImage, Rgba, RgbaImage,
+};
+use log::{debug, error, info, warn, LevelFilter};
+use serde::{Deserialize, Serialize};
+use sierpinski_triangle::{
+    box_dimension, get_color, get_positions_toward, hsv_to_rgb, ifs_bounds, interpolate,
+    make_antialiased, make_carpet, make_checkpoints, make_cropped, make_fern, make_frames,
+    make_heatmap, make_ifs, make_image, make_layers, make_palette, make_points, make_tetrahedron,
+    make_tiles, rotate_positions, value_noise, widen, Apex, Colormap, CornerShape, Generator,
+    HitCounts, Map, Mask, Options, Restriction,
+};
+
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Cursor, IsTerminal, Read, Seek, Write};
+use std::path::{Path, PathBuf};
 use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Once};
+use std::time::Instant;
+
+mod batch;
+mod interactive;
+mod palette;
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct Cli {
     #[clap(subcommand)]
     command: Commands,
+
+    /// Only log warnings and errors, and hide the progress bar
+    #[clap(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Show the progress even when stderr isn't a terminal (Logged every few seconds instead of as a bar)
+    #[clap(long, global = true, conflicts_with = "quiet")]
+    progress: bool,
+
+    /// Also log debug messages
+    #[clap(short, long, global = true)]
+    verbose: bool,
+
+    /// Exit with an error on invalid colors instead of using white
+    #[clap(long, global = true)]
+    strict: bool,
+
+    /// Refuse to make images needing more memory than this (Like 512M or 4G)
+    #[clap(long, global = true, value_name = "SIZE", value_parser = parse_size)]
+    max_memory: Option<u64>,
+
+    /// How the log messages are written
+    #[clap(long, global = true, value_enum, default_value = "text")]
+    log_format: LogFormat,
+
+    /// Place more than a billion dots anyway, instead of stopping as it's likely a typo
+    #[clap(short, long, global = true)]
+    yes: bool,
+}
+
+/// How the log messages are written
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum LogFormat {
+    /// Lines with the level in brackets, like `[INFO] Saving image`
+    Text,
+
+    /// A JSON object on each line with the timestamp, level and message, for log pipelines
+    Json,
+}
+
+/// Options for saving the image, shared by the subcommands
+///
+/// Like the options of generate, each of them can also be set with the environment variable shown
+/// next to it.
+#[derive(Args, Debug)]
+struct Save {
+    /// The path of the output image (Use - to write it to stdout)
+    ///
+    /// Paths ending in .svg give a vector image with a rectangle for every run of dots, which gets
+    /// large quickly, so keep it to at most a few hundred thousand dots.
+    #[clap(short, long, name = "FILE", env = "SIERPINSKI_OUTPUT")]
+    output: Option<String>,
+
+    /// Directory to save the image in when no output is given, made if it doesn't exist yet
+    #[clap(
+        long,
+        value_name = "DIR",
+        conflicts_with = "FILE",
+        env = "SIERPINSKI_OUTPUT_DIR"
+    )]
+    output_dir: Option<PathBuf>,
+
+    /// Format of the output image when no path is given or writing to stdout (Like png, jpg, webp, bmp or tiff)
+    ///
+    /// WebP images are usually far smaller than PNG images.
+    #[clap(long, value_name = "FMT", default_value = "png", value_parser = parse_format, env = "SIERPINSKI_FORMAT")]
+    format: ImageFormat,
+
+    /// Quality of JPEG and WebP images, trading size for detail (From 0 to 100, with WebP images lossless without it)
+    #[clap(long, value_parser = clap::value_parser!(u8).range(0..=100), env = "SIERPINSKI_QUALITY")]
+    quality: Option<u8>,
+
+    /// Overwrite the output file if it already exists
+    #[clap(short, long, env = "SIERPINSKI_FORCE")]
+    force: bool,
+
+    /// Add the current date and time to the default file name, so earlier images aren't overwritten
+    #[clap(long, env = "SIERPINSKI_TIMESTAMP")]
+    timestamp: bool,
+
+    /// Set the generated image as wallpaper
+    #[clap(long, env = "SIERPINSKI_WALLPAPER")]
+    wallpaper: bool,
+
+    /// How the desktop fits the wallpaper to the screen (Not supported everywhere)
+    #[clap(
+        long,
+        value_enum,
+        value_name = "MODE",
+        requires = "wallpaper",
+        env = "SIERPINSKI_WALLPAPER_MODE"
+    )]
+    wallpaper_mode: Option<WallpaperMode>,
+
+    /// Invert the colors of the finished image, like a negative
+    #[clap(long, env = "SIERPINSKI_INVERT")]
+    invert: bool,
+
+    /// Scale the finished image up by this factor, keeping the dots sharp
+    #[clap(long, value_name = "FACTOR", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..), env = "SIERPINSKI_SCALE")]
+    scale: u32,
+
+    /// Frame the finished image with a border this wide, drawn over its outer pixels (In pixels)
+    #[clap(
+        long,
+        value_name = "PX",
+        default_value_t = 0,
+        env = "SIERPINSKI_BORDER"
+    )]
+    border: u32,
+
+    /// The color of the --border (In hex format or a CSS color name)
+    #[clap(long, value_name = "HEX", default_value = "white", value_parser = parse_hex_color, env = "SIERPINSKI_BORDER_COLOR")]
+    border_color: Rgba<u8>,
+
+    /// Open the saved image in the default image viewer
+    #[clap(long, env = "SIERPINSKI_PREVIEW")]
+    preview: bool,
+
+    /// Also copy the image to the clipboard
+    #[clap(long, env = "SIERPINSKI_CLIPBOARD")]
+    clipboard: bool,
+
+    /// Also save the settings the image was made with to a JSON file, named like the image plus .json
+    #[clap(long, env = "SIERPINSKI_METADATA")]
+    metadata: bool,
+
+    /// The settings saved with --metadata and in PNG images, filled in by the subcommand
+    #[clap(skip)]
+    settings: Option<Metadata>,
+
+    /// Which of the images made with --repeat this is, added to the end of its file name
+    #[clap(skip)]
+    index: Option<u32>,
+
+    /// How many times larger the image is made than it's saved (With --supersample, 0 or 1 for the same size)
+    #[clap(skip)]
+    supersample: u32,
+
+    /// Print the image to the terminal as text instead of saving it (With --ascii)
+    #[clap(skip)]
+    ascii: bool,
+
+    /// The directory every frame of an animation is also saved to as a numbered PNG (With --frames-dir)
+    #[clap(skip)]
+    frames_dir: Option<PathBuf>,
+
+    /// Only print what would be made and where it would be saved, without making it
+    #[clap(long, env = "SIERPINSKI_DRY_RUN")]
+    dry_run: bool,
+}
+
+/// The settings an image was made with, as saved with --metadata and in PNG images
+#[derive(Serialize, Debug)]
+struct Metadata {
+    version: &'static str,
+    width: u32,
+    height: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dots: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    iterations: Option<u32>,
+    color: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    restriction: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ratio: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    weights: Option<Vec<f64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    supersample: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rng: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    vertices: Option<u32>,
+}
+
+/// The weights of the vertices, as parsed from a comma separated list
+///
+/// This wraps the list, as clap would otherwise take a `Vec` for a repeated option.
+#[derive(Clone, Debug)]
+struct Weights(Vec<f64>);
+
+/// What the chaos game is played on
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    /// A flat polygon (The Sierpiński triangle with the default 3 vertices)
+    Triangle,
+
+    /// A regular tetrahedron in 3D, projected onto the image
+    Tetrahedron,
+}
+
+/// A size and number of dots that look good together, for common uses
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Preset {
+    /// 1920x1080 with 2000000 dots
+    Hd,
+
+    /// 3840x2160 with 8000000 dots
+    #[clap(name = "4k")]
+    FourK,
+
+    /// 1080x1920 with 2000000 dots, for the screen of a phone
+    Mobile,
+
+    /// 2048x2048 with 4000000 dots
+    Square,
+}
+
+impl Preset {
+    /// The width, height and number of dots of the preset
+    fn bundle(self) -> (u32, u32, u64) {
+        match self {
+            Preset::Hd => (1920, 1080, 2_000_000),
+            Preset::FourK => (3840, 2160, 8_000_000),
+            Preset::Mobile => (1080, 1920, 2_000_000),
+            Preset::Square => (2048, 2048, 4_000_000),
+        }
+    }
+}
+
+/// How the wallpaper is fitted to the screen, as in [`wallpaper::Mode`]
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum WallpaperMode {
+    /// Keep the image at its size in the middle of the screen
+    Center,
+
+    /// Scale the image to fill the screen, cutting off what doesn't fit
+    Crop,
+
+    /// Scale the image to fit inside the screen
+    Fit,
+
+    /// Stretch the image over all screens
+    Span,
+
+    /// Stretch the image to the size of the screen
+    Stretch,
+
+    /// Repeat the image over the screen
+    Tile,
+}
+
+impl From<WallpaperMode> for wallpaper::Mode {
+    fn from(mode: WallpaperMode) -> Self {
+        match mode {
+            WallpaperMode::Center => wallpaper::Mode::Center,
+            WallpaperMode::Crop => wallpaper::Mode::Crop,
+            WallpaperMode::Fit => wallpaper::Mode::Fit,
+            WallpaperMode::Span => wallpaper::Mode::Span,
+            WallpaperMode::Stretch => wallpaper::Mode::Stretch,
+            WallpaperMode::Tile => wallpaper::Mode::Tile,
+        }
+    }
+}
+
+/// How the dots are turned into the saved image
+#[derive(Clone, Copy)]
+enum Render {
+    /// A single image of the dots
+    Image,
+
+    /// An animation of the dots being placed, with this many frames
+    Animation(u32),
+
+    /// A tetrahedron turned `rotate` degrees, with the dots darkened by depth if `shade` is on
+    Tetrahedron { rotate: f64, shade: bool },
+
+    /// An animation of the polygon (Or the tetrahedron, turned `rotate` degrees to start with)
+    /// turning a full circle, with this many frames
+    Spin {
+        frames: u32,
+        tetrahedron: bool,
+        rotate: f64,
+        shade: bool,
+    },
+
+    /// A heatmap of how often each pixel was hit
+    Heatmap,
+
+    /// A single image with the dots colored by the vertex they last jumped towards
+    Palette,
+
+    /// A single image with the dots spread smoothly over the pixels around them
+    Antialiased,
+
+    /// A grid of columns and rows of tiles with their own dots, every other one upside down with `flip`
+    Tiles { grid: [u32; 2], flip: bool },
+
+    /// A single image, saved to a partial file every this many dots while it's made
+    Checkpoints(u64),
+
+    /// A single image cut down to the smallest rectangle around the dots
+    Cropped,
+
+    /// This many triangles on top of each other with their vertices moved a little, colored by the
+    /// palette when `palette` is on
+    Layers { layers: u32, palette: bool },
 }
 
+// The commands are only parsed once, so the size of the largest one doesn't matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Generate a new Sierpiński triangle
+    ///
+    /// Every option below can also be set with the environment variable shown next to it, which is
+    /// used when the option isn't given on the command line (Flags are turned on by setting them to
+    /// true, and --gradient, --radial and --vertex take their values separated by spaces).
+    // `-h` is taken by `--height`, so help is only available as `--help`
+    #[clap(arg(clap::Arg::new("help").long("help").action(clap::ArgAction::Help).help("Print help information")))]
     Generate {
+        /// Width of the image (In pixels, the width of the screen with --wallpaper)
+        #[clap(short, long, env = "SIERPINSKI_WIDTH")]
+        width: Option<u32>,
+
+        /// Height of the image (In pixels, the height of the screen with --wallpaper)
+        #[clap(short, long, env = "SIERPINSKI_HEIGHT")]
+        height: Option<u32>,
+
+        /// Width to height ratio of the image (Like 16:9), giving the --width or --height left out
+        #[clap(long, value_name = "W:H", value_parser = parse_aspect, env = "SIERPINSKI_ASPECT")]
+        aspect: Option<[f64; 2]>,
+
+        /// Number of dots to draw on the image
+        #[clap(
+            short,
+            long,
+            required_unless_present = "preset",
+            env = "SIERPINSKI_DOTS"
+        )]
+        dots: Option<u64>,
+
+        /// Start from the size and number of dots of a preset, which --width, --height and --dots override
+        #[clap(long, value_enum, value_name = "NAME", env = "SIERPINSKI_PRESET")]
+        preset: Option<Preset>,
+
+        /// The color of the pixels being placed (In hex format, a CSS color name, or auto for the accent color of the desktop or the text color of the terminal)
+        #[clap(short, long, env = "SIERPINSKI_COLOR")]
+        color: Option<String>,
+
+        /// Fade the color of the dots from one color to another as they are placed (In hex format or CSS color names)
+        #[clap(short, long, number_of_values = 2, value_names = &["FROM", "TO"], conflicts_with = "color", env = "SIERPINSKI_GRADIENT", value_delimiter = ' ')]
+        gradient: Option<Vec<String>>,
+
+        /// Fade the color of the dots from one color in the center of the vertices to another at the farthest vertex (In hex format or CSS color names)
+        #[clap(long, number_of_values = 2, value_names = &["INNER", "OUTER"], conflicts_with_all = &["color", "gradient"], env = "SIERPINSKI_RADIAL", value_delimiter = ' ')]
+        radial: Option<Vec<String>>,
+
+        /// Color the dots by the vertex they last jumped towards, with one color for every vertex (In hex format or CSS color names, and one color for every layer with --layers)
+        #[clap(long, value_name = "COLORS", value_delimiter = ',', conflicts_with_all = &["color", "gradient", "radial", "corner-color"], env = "SIERPINSKI_PALETTE")]
+        palette: Option<Vec<String>>,
+
+        /// Color the dots by the colors of a GIMP palette or a file with a color on every line, cycling through them dot by dot
+        #[clap(long, value_name = "PATH", conflicts_with_all = &["color", "gradient", "radial", "palette", "corner-color"], env = "SIERPINSKI_PALETTE_FILE")]
+        palette_file: Option<String>,
+
+        /// Color each dot like the pixel under it of this image, stretched over the whole image, without drawing on it
+        #[clap(long, value_name = "PATH", conflicts_with_all = &["color", "gradient", "radial", "palette", "palette-file"], env = "SIERPINSKI_COLOR_SOURCE")]
+        color_source: Option<String>,
+
+        /// Color the dots by where they are between the vertices, with the hue going around the rainbow from left to right and the saturation rising from top to bottom (Only --corner-color is still used with it)
+        #[clap(long, conflicts_with_all = &["color", "gradient", "radial", "palette", "palette-file", "color-source"], env = "SIERPINSKI_HUE_MAP")]
+        hue_map: bool,
+
+        /// Color the dots by the colors of --palette-file like --palette instead, going around them for more vertices than colors
+        #[clap(long, requires = "palette-file", env = "SIERPINSKI_BY_VERTEX")]
+        by_vertex: bool,
+
+        /// The color of the background (In hex format or a CSS color name, black by default)
+        #[clap(short, long, env = "SIERPINSKI_BACKGROUND")]
+        background: Option<String>,
+
+        /// Leave the background transparent, so only the dots are opaque
+        #[clap(long, env = "SIERPINSKI_TRANSPARENT")]
+        transparent: bool,
+
+        /// Texture the background with smooth noise around its color, made from the --seed
+        #[clap(long, env = "SIERPINSKI_BG_NOISE")]
+        bg_noise: bool,
+
+        /// Draw on this image as it is instead of an empty background, at its own size (A path, an http:// or https:// URL to download it from, or - to read it from stdin)
+        #[clap(long, value_name = "PATH", env = "SIERPINSKI_BASE")]
+        base: Option<String>,
+
+        /// Bits per color channel (8 or 16, which gives smoother gradients and heatmaps but can only be saved as PNG or TIFF)
+        #[clap(long, value_name = "BITS", default_value_t = 8, value_parser = parse_depth, env = "SIERPINSKI_DEPTH")]
+        depth: u8,
+
+        /// Don't mark the vertices with a dot
+        #[clap(long, env = "SIERPINSKI_NO_CORNERS")]
+        no_corners: bool,
+
+        /// The color of the dots marking the vertices, instead of the color of the first dot (In hex format or a CSS color name)
+        #[clap(
+            long,
+            value_name = "HEX",
+            conflicts_with = "no-corners",
+            env = "SIERPINSKI_CORNER_COLOR"
+        )]
+        corner_color: Option<String>,
+
+        /// Width and height of the marks on the vertices (In pixels)
+        #[clap(long, value_name = "R", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..), conflicts_with = "no-corners", env = "SIERPINSKI_CORNER_SIZE")]
+        corner_size: u32,
+
+        /// Shape of the marks on the vertices
+        #[clap(long, value_enum, default_value_t = CornerShape::Dot, conflicts_with = "no-corners", env = "SIERPINSKI_CORNER_SHAPE")]
+        corner_shape: CornerShape,
+
+        #[clap(flatten)]
+        save: Save,
+
+        /// Seed for the random number generator (For reproducible images)
+        #[clap(short, long, env = "SIERPINSKI_SEED")]
+        seed: Option<u64>,
+
+        /// Number of vertices of the polygon the chaos game is played on
+        ///
+        /// The vertices are placed evenly around a circle, except for 3 which gives the usual
+        /// triangle. Note that with 4 vertices the dots simply fill the whole square.
+        #[clap(long, default_value_t = 3, value_parser = clap::value_parser!(u32).range(3..), env = "SIERPINSKI_VERTICES")]
+        vertices: u32,
+
+        /// Coordinates of a vertex, overriding the computed ones (Repeat once for every vertex)
+        #[clap(long, value_name = "X,Y", value_parser = parse_vertex, env = "SIERPINSKI_VERTEX", value_delimiter = ' ')]
+        vertex: Vec<[u32; 2]>,
+
+        /// Where the dots start jumping from, instead of the first vertex (Dots from points off the fractal stray until --skip hides them)
+        #[clap(long, value_name = "X,Y", value_parser = parse_vertex, env = "SIERPINSKI_START")]
+        start: Option<[u32; 2]>,
+
+        /// Restriction on which vertex can be chosen next
+        #[clap(
+            short,
+            long,
+            value_enum,
+            default_value = "none",
+            env = "SIERPINSKI_RESTRICT"
+        )]
+        restrict: Restriction,
+
+        /// How likely each vertex is to be chosen relative to the others, instead of all equally (One weight for every vertex)
+        #[clap(long, value_name = "W1,W2,...", value_parser = parse_weights, env = "SIERPINSKI_WEIGHTS")]
+        weights: Option<Weights>,
+
+        /// Play the chaos game on a flat polygon or in 3D on a tetrahedron
+        #[clap(
+            short,
+            long,
+            value_enum,
+            default_value = "triangle",
+            env = "SIERPINSKI_MODE"
+        )]
+        mode: Mode,
+
+        /// Turn the polygon clockwise around the center of the image, or the tetrahedron around its vertical axis (In degrees)
+        #[clap(
+            long,
+            value_name = "DEG",
+            default_value_t = 0.0,
+            allow_hyphen_values = true,
+            env = "SIERPINSKI_ROTATE"
+        )]
+        rotate: f64,
+
+        /// The side of the image the polygon points towards, before any --rotate
+        #[clap(long, value_enum, default_value_t = Apex::Top, conflicts_with = "vertex", env = "SIERPINSKI_APEX")]
+        apex: Apex,
+
+        /// Make the dots of the tetrahedron darker the farther back they are
+        #[clap(long, env = "SIERPINSKI_SHADE")]
+        shade: bool,
+
+        /// How far each dot jumps towards the chosen vertex (Strictly between 0 and 1)
+        #[clap(long, default_value_t = 0.5, value_parser = parse_ratio, env = "SIERPINSKI_RATIO")]
+        ratio: f64,
+
+        /// Space between the vertices and the edges of the image, as a fraction of its width and height (From 0 to 0.4)
+        #[clap(long, value_name = "FRACTION", default_value_t = 0.1, value_parser = parse_margin, env = "SIERPINSKI_MARGIN")]
+        margin: f64,
+
+        /// Number of dots to jump without drawing first, hiding the stray dots from a --start off the fractal
+        #[clap(long, value_name = "N", default_value_t = 10, env = "SIERPINSKI_SKIP")]
+        skip: u64,
+
+        /// Width and height of each dot (In pixels)
+        #[clap(long, value_name = "R", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..), env = "SIERPINSKI_DOT_SIZE")]
+        dot_size: u32,
+
+        /// Move every dot by up to this many pixels in each direction at random, for a sketchy look
+        #[clap(
+            long,
+            value_name = "PX",
+            default_value_t = 0,
+            env = "SIERPINSKI_JITTER"
+        )]
+        jitter: u32,
+
+        /// The random number generator placing the dots (fast is quicker but not as random, and places other dots for the same --seed)
+        #[clap(long, value_enum, value_name = "KIND", default_value_t = Generator::Standard, env = "SIERPINSKI_RNG")]
+        rng: Generator,
+
+        /// Number of threads to place the dots on (Hitting the same pixels as a single thread with the same --seed)
+        #[clap(short, long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..), env = "SIERPINSKI_THREADS")]
+        threads: u32,
+
+        /// Save an animated GIF with this many frames showing the dots being placed
+        #[clap(short, long, value_name = "FRAMES", value_parser = clap::value_parser!(u32).range(1..), env = "SIERPINSKI_ANIMATE")]
+        animate: Option<u32>,
+
+        /// Save an animation of the polygon or tetrahedron turning a full circle over this many seconds, as a GIF or, when --output ends in .png or .mp4, an animated PNG or (With ffmpeg) an MP4
+        ///
+        /// Every second is 25 frames, which are all kept in memory until they're saved, so a 1000x1000 image
+        /// spinning for 4 seconds takes about 300 MB. Animated PNGs and GIFs end up about as large as that
+        /// many separate images, while MP4s are far smaller.
+        #[clap(long, value_name = "SECONDS", value_parser = parse_seconds, conflicts_with = "animate", env = "SIERPINSKI_SPIN")]
+        spin: Option<f64>,
+
+        /// Save every frame of --animate or --spin as a numbered PNG in this directory (Like frame-0000.png), instead of the animation unless --output is given too
+        #[clap(long, value_name = "DIR", env = "SIERPINSKI_FRAMES_DIR")]
+        frames_dir: Option<PathBuf>,
+
+        /// Color the pixels by how often they were hit instead, showing the density of the fractal
+        #[clap(long, env = "SIERPINSKI_HEATMAP")]
+        heatmap: bool,
+
+        /// The color scale of the heatmap
+        #[clap(
+            long,
+            value_enum,
+            default_value = "viridis",
+            env = "SIERPINSKI_COLORMAP"
+        )]
+        colormap: Colormap,
+
+        /// Split the image into a grid of tiles with a triangle of --dots dots in each of them
+        #[clap(long, value_name = "COLSxROWS", value_parser = parse_tile, env = "SIERPINSKI_TILE")]
+        tile: Option<[u32; 2]>,
+
+        /// Turn every other tile upside down
+        #[clap(long, requires = "tile", env = "SIERPINSKI_FLIP_TILES")]
+        flip_tiles: bool,
+
+        /// Draw this many triangles of --dots dots on top of each other, with the vertices moved a little for each of them
+        #[clap(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..), env = "SIERPINSKI_LAYERS")]
+        layers: Option<u32>,
+
+        /// Spread every dot over the pixels around its exact position, smoothing the edges
+        #[clap(long, env = "SIERPINSKI_ANTIALIAS")]
+        antialias: bool,
+
+        /// Save the unfinished image every N dots, to the output path plus .partial.png (Removed when it's finished)
+        #[clap(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..), env = "SIERPINSKI_CHECKPOINT")]
+        checkpoint: Option<u64>,
+
+        /// Cut the image down to the smallest rectangle around the triangle, leaving out the margin
+        #[clap(long, env = "SIERPINSKI_CROP")]
+        crop: bool,
+
+        /// Make the image this many times wider and higher with the square of this times the dots, and shrink it back when it's saved to smooth the dots (Taking the square of this times the memory)
+        #[clap(long, value_name = "FACTOR", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=16), env = "SIERPINSKI_SUPERSAMPLE")]
+        supersample: u32,
+
+        /// Estimate the fractal dimension of the finished image by counting boxes, and log it
+        #[clap(long, env = "SIERPINSKI_MEASURE_DIMENSION")]
+        measure_dimension: bool,
+
+        /// Print the image to the terminal as text instead of saving it, shrinking --width and --height down to a character for every few pixels across the terminal
+        #[clap(long, env = "SIERPINSKI_ASCII")]
+        ascii: bool,
+
+        /// Log how many pixels the dots hit, how often the busiest of them was hit and how much of the image they cover
+        #[clap(long, env = "SIERPINSKI_STATS")]
+        stats: bool,
+
+        /// Only let the dots land on the light pixels of this image (Stretched over the whole image), so the fractal fills its shape
+        #[clap(long, value_name = "PATH", env = "SIERPINSKI_MASK")]
+        mask: Option<String>,
+
+        /// Make this many images, adding 1 to the seed and a number to the file name for each of them
+        #[clap(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..), env = "SIERPINSKI_REPEAT")]
+        repeat: u32,
+
+        /// Also write the coordinates of every dot to this CSV file, only making the image too when an --output is given
+        #[clap(long, value_name = "CSV", env = "SIERPINSKI_EXPORT_POINTS")]
+        export_points: Option<String>,
+    },
+
+    /// Add a Sierpiński triangle to an image
+    Image {
+        /// The image to add a Sierpiński triangle to (A path, an http:// or https:// URL to download it from, or - to read it from stdin)
+        image: String,
+
+        /// Number of dots to draw on the image
+        #[clap(short, long)]
+        dots: u64,
+
+        /// The color of the pixels being placed, instead of the colors of the image (In hex format or a CSS color name)
+        #[clap(short, long)]
+        color: Option<String>,
+
+        /// Don't mark the vertices with a dot
+        #[clap(long)]
+        no_corners: bool,
+
+        /// The color of the dots marking the vertices, instead of the color of the dots (In hex format or a CSS color name)
+        #[clap(long, value_name = "HEX", conflicts_with = "no-corners")]
+        corner_color: Option<String>,
+
+        /// How much the image is brightened behind the dots (Negative values darken it)
+        #[clap(long, value_name = "AMOUNT", default_value_t = -50, allow_hyphen_values = true)]
+        darken: i32,
+
+        /// Turn the image gray behind the dots (The default)
+        #[clap(long, overrides_with = "no-grayscale")]
+        grayscale: bool,
+
+        /// Keep the colors of the image behind the dots
+        #[clap(long, overrides_with = "grayscale")]
+        no_grayscale: bool,
+
+        #[clap(flatten)]
+        save: Save,
+
+        /// Seed for the random number generator (For reproducible images)
+        #[clap(short, long)]
+        seed: Option<u64>,
+
+        /// Number of dots to jump without drawing first
+        #[clap(long, value_name = "N", default_value_t = 10)]
+        skip: u64,
+
+        /// Width and height of each dot (In pixels)
+        #[clap(long, value_name = "R", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+        dot_size: u32,
+
+        /// Number of threads to place the dots on (Hitting the same pixels as a single thread with the same --seed)
+        #[clap(short, long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+        threads: u32,
+    },
+
+    /// Generate a Barnsley fern with the same chaos game
+    #[clap(arg(clap::Arg::new("help").long("help").action(clap::ArgAction::Help).help("Print help information")))]
+    Fern {
         /// Width of the image (In pixels)
         #[clap(short, long)]
         width: u32,
@@ -34,215 +700,3496 @@ enum Commands {
         #[clap(short, long)]
         dots: u64,
 
-        /// The path of the output image
-        #[clap(short, long, name = "FILE")]
-        output: Option<String>,
-
-        /// The color of the pixels being placed (In hex format)
+        /// The color of the pixels being placed (In hex format or a CSS color name)
         #[clap(short, long)]
         color: Option<String>,
 
-        /// Set the generated image as wallpaper
-        #[clap(long)]
-        wallpaper: bool,
+        /// The color of the background (In hex format or a CSS color name, black by default)
+        #[clap(short, long)]
+        background: Option<String>,
+
+        #[clap(flatten)]
+        save: Save,
+
+        /// Seed for the random number generator (For reproducible images)
+        #[clap(short, long)]
+        seed: Option<u64>,
+
+        /// Number of dots to jump without drawing first
+        #[clap(long, value_name = "N", default_value_t = 10)]
+        skip: u64,
+
+        /// Width and height of each dot (In pixels)
+        #[clap(long, value_name = "R", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+        dot_size: u32,
     },
 
-    /// Add a Sierpiński triangle to an image
-    Image {
-        /// The image to add a Sierpiński triangle to
-        image: String,
+    /// Generate the fractal of an iterated function system, with its affine maps read from a file
+    ///
+    /// The file is a list of maps with a `transform` of [a, b, c, d, e, f], taking x,y to ax + by + e,
+    /// cx + dy + f, and the `probability` of choosing it, which add up to 1. It's read as a JSON list
+    /// if it ends in .json, and as [[map]] tables in TOML otherwise.
+    #[clap(arg(clap::Arg::new("help").long("help").action(clap::ArgAction::Help).help("Print help information")))]
+    Ifs {
+        /// The TOML or JSON file with the maps
+        config: String,
+
+        /// Width of the image (In pixels)
+        #[clap(short, long)]
+        width: u32,
+
+        /// Height of the image (In pixels)
+        #[clap(short, long)]
+        height: u32,
 
         /// Number of dots to draw on the image
         #[clap(short, long)]
         dots: u64,
 
-        /// The path of the output image
-        #[clap(short, long, name = "FILE")]
-        output: Option<String>,
+        /// The color of the pixels being placed (In hex format or a CSS color name)
+        #[clap(short, long)]
+        color: Option<String>,
+
+        /// The color of the background (In hex format or a CSS color name, black by default)
+        #[clap(short, long)]
+        background: Option<String>,
 
-        /// Set the generated image as wallpaper
-        #[clap(long)]
-        wallpaper: bool,
+        #[clap(flatten)]
+        save: Save,
+
+        /// Seed for the random number generator (For reproducible images)
+        #[clap(short, long)]
+        seed: Option<u64>,
+
+        /// Number of dots to jump without drawing first
+        #[clap(long, value_name = "N", default_value_t = 10)]
+        skip: u64,
+
+        /// Width and height of each dot (In pixels)
+        #[clap(long, value_name = "R", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+        dot_size: u32,
+    },
+
+    /// Generate a Sierpiński carpet by cutting the middle out of squares
+    #[clap(arg(clap::Arg::new("help").long("help").action(clap::ArgAction::Help).help("Print help information")))]
+    Carpet {
+        /// Width of the image (In pixels)
+        #[clap(short, long)]
+        width: u32,
+
+        /// Height of the image (In pixels)
+        #[clap(short, long)]
+        height: u32,
+
+        /// Number of times to cut the middle out of every square
+        #[clap(short, long, default_value_t = 5, value_parser = clap::value_parser!(u32).range(0..=20))]
+        iterations: u32,
+
+        /// The color of the carpet (In hex format or a CSS color name)
+        #[clap(short, long)]
+        color: Option<String>,
+
+        /// The color of the background (In hex format or a CSS color name, black by default)
+        #[clap(short, long)]
+        background: Option<String>,
+
+        #[clap(flatten)]
+        save: Save,
+    },
+
+    /// Try out the number of dots, color and seed with a preview in the terminal, saving the image with s
+    ///
+    /// The arrow keys up and down double and halve the dots, left and right step through the
+    /// seeds, r picks a random seed and c goes to the next color. Quit with q or Escape.
+    #[clap(arg(clap::Arg::new("help").long("help").action(clap::ArgAction::Help).help("Print help information")))]
+    Interactive {
+        /// Width of the image (In pixels)
+        #[clap(short, long)]
+        width: u32,
+
+        /// Height of the image (In pixels)
+        #[clap(short, long)]
+        height: u32,
+
+        /// Number of dots to start with
+        #[clap(short, long, default_value_t = 100_000)]
+        dots: u64,
+
+        /// The color to start with (In hex format or a CSS color name)
+        #[clap(short, long)]
+        color: Option<String>,
+
+        #[clap(flatten)]
+        save: Save,
+
+        /// Seed to start with, instead of a random one
+        #[clap(short, long)]
+        seed: Option<u64>,
+    },
+
+    /// Print the settings saved in a PNG image made by this program
+    Info {
+        /// The PNG image to read
+        file: String,
+    },
+
+    /// Check a PNG image made by this program matches its saved settings by making it again
+    ///
+    /// Prints whether it passed and exits with an error when it didn't. Only the size, dots,
+    /// color, seed, vertices, restriction, ratio, weights, random number generator and
+    /// supersampling are saved, so images made with anything else changed (Like --margin,
+    /// --background or --scale) won't match.
+    Verify {
+        /// The PNG image to check
+        file: String,
+    },
+
+    /// Print a script completing the subcommands and options in a shell
+    ///
+    /// Source the output in the shell, or save it where the shell loads completions from (Like
+    /// ~/.local/share/bash-completion/completions/sierpinski-triangle for bash).
+    Completions {
+        /// The shell to complete in
+        #[clap(value_enum)]
+        shell: clap_complete::Shell,
+    },
+
+    /// Generate Sierpiński triangles for every job in a file
+    ///
+    /// The file is a list of jobs with a width, height and number of dots, and optionally a color,
+    /// output and seed. JSON files (Ending in .json) hold a list of objects, other files are read
+    /// as TOML with a [[job]] table for every job.
+    Batch {
+        /// The file with the jobs
+        file: String,
+
+        /// Overwrite output files that already exist
+        #[clap(short, long)]
+        force: bool,
     },
 }
 
 fn main() {
+    let start_time = Instant::now();
     let args = Cli::parse();
 
     let mut builder = Builder::new();
 
-    builder
-        .format(|buf, record| {
+    match args.log_format {
+        LogFormat::Text => builder.format(|buf, record| {
             writeln!(
                 buf,
                 "[{}] {}",
                 buf.default_styled_level(record.level()),
                 record.args()
             )
-        })
-        .filter(None, LevelFilter::Info)
+        }),
+        LogFormat::Json => builder.format(|buf, record| {
+            let line = serde_json::json!({
+                "timestamp": Local::now().to_rfc3339(),
+                "level": record.level().as_str(),
+                "message": record.args().to_string(),
+            });
+            writeln!(buf, "{line}")
+        }),
+    };
+    builder
+        .filter(
+            None,
+            if args.quiet {
+                LevelFilter::Warn
+            } else if args.verbose {
+                LevelFilter::Debug
+            } else {
+                LevelFilter::Info
+            },
+        )
         .init();
 
+    // Progress bars only make a mess of anything but a terminal, like a log file
+    let progress = !args.quiet && (args.progress || io::stderr().is_terminal());
+
     match args.command {
         Commands::Generate {
             width,
             height,
+            aspect,
             dots,
-            output,
+            preset,
             color,
-            wallpaper,
-        } => {
-            let col = get_color(color);
-            let img = make_image(RgbImage::new(width, height), dots, |_, _| col);
-
-            handle_image(img, dots, output, wallpaper);
-        }
-        Commands::Image {
-            image,
-            dots,
-            output,
-            wallpaper,
+            gradient,
+            radial,
+            palette,
+            palette_file,
+            color_source,
+            hue_map,
+            by_vertex,
+            background,
+            transparent,
+            bg_noise,
+            base,
+            depth,
+            no_corners,
+            corner_color,
+            corner_size,
+            corner_shape,
+            mut save,
+            seed,
+            vertices,
+            vertex,
+            start,
+            restrict,
+            weights,
+            mode,
+            rotate,
+            apex,
+            shade,
+            ratio,
+            margin,
+            skip,
+            dot_size,
+            jitter,
+            rng,
+            threads,
+            animate,
+            spin,
+            frames_dir,
+            heatmap,
+            colormap,
+            tile,
+            flip_tiles,
+            layers,
+            antialias,
+            checkpoint,
+            crop,
+            supersample,
+            measure_dimension,
+            ascii,
+            stats,
+            mask,
+            repeat,
+            export_points,
         } => {
-            info!("Reading {image}");
-            let im = image::open(&image).unwrap_or_else(|err| {
-                error!("Couldn't read file {image}: {err}");
-                process::exit(1);
-            });
-
-            let img = make_image(im.grayscale().brighten(-50).to_rgb8(), dots, |x, y| {
-                let px = im.get_pixel(x, y);
-                Rgb::from([px[0], px[1], px[2]])
+            // The preset fills in what isn't given, though only the width with --aspect, which
+            // works out the height from it
+            let (width, height, dots) = match preset.map(Preset::bundle) {
+                Some((preset_width, preset_height, preset_dots)) => {
+                    let (width, height) = match aspect {
+                        None => (width.or(Some(preset_width)), height.or(Some(preset_height))),
+                        Some(_) if width.is_none() && height.is_none() => {
+                            (Some(preset_width), None)
+                        }
+                        Some(_) => (width, height),
+                    };
+                    (width, height, dots.unwrap_or(preset_dots))
+                }
+                // Clap requires --dots without a preset
+                None => (width, height, dots.unwrap_or_default()),
+            };
+            let base = base.map(|path| {
+                read_image(&path).unwrap_or_else(|err| {
+                    error!("Couldn't read the base image {path}: {err}");
+                    process::exit(1);
+                })
             });
+            if base.is_some() && (background.is_some() || bg_noise) {
+                warn!("The --base is the background, ignoring --background and --bg-noise");
+            }
+            let (width, height) = match (&base, width, height, aspect) {
+                (Some(base), width, height, aspect) => {
+                    if width.is_some() || height.is_some() || aspect.is_some() {
+                        warn!("The image is the size of the --base, ignoring --width, --height and --aspect");
+                    }
+                    base.dimensions()
+                }
+                (None, width, height, Some(aspect)) => aspect_size(width, height, aspect)
+                    .unwrap_or_else(|err| {
+                        error!("{err}");
+                        process::exit(1);
+                    }),
+                (None, Some(width), Some(height), None) => (width, height),
+                (None, width, height, None) if save.wallpaper => {
+                    let (screen_width, screen_height) = screen_size();
+                    (
+                        width.unwrap_or(screen_width),
+                        height.unwrap_or(screen_height),
+                    )
+                }
+                _ => {
+                    error!(
+                        "The --width and --height of the image are required without --wallpaper"
+                    );
+                    process::exit(1);
+                }
+            };
+            let channels = if transparent { 4 } else { 3 };
+            check_dimensions(
+                width,
+                height,
+                channels * u128::from(depth / 8),
+                args.max_memory,
+            );
+            if depth == 16 {
+                check_depth(&save);
+            }
 
-            handle_image(img, dots, output, wallpaper);
-        }
-    }
-}
+            let weights = weights.map(|Weights(weights)| weights);
+            if let Some(weights) = &weights {
+                let count = match mode {
+                    Mode::Triangle => vertices as usize,
+                    Mode::Tetrahedron => 4,
+                };
+                check_weights(weights, count, restrict);
+            }
 
-fn handle_image(
-    img: ImageBuffer<Rgb<u8>, Vec<u8>>,
+            let positions = if vertex.is_empty() {
+                None
+            } else {
+                check_vertices(&vertex, vertices as usize, width, height);
+                Some(vertex)
+            };
+            // The tetrahedron is turned in 3D instead, when it's drawn
+            let positions = if rotate != 0.0 && mode == Mode::Triangle {
+                let positions = positions.unwrap_or_else(|| {
+                    get_positions_toward(width, height, vertices as usize, margin, apex)
+                });
+                Some(rotate_positions(&positions, rotate, width, height))
+            } else {
+                positions
+            };
+            if let Some([x, y]) = start {
+                if x >= width || y >= height {
+                    error!("The start {x},{y} is outside of the {width}x{height} image");
+                    process::exit(1);
+                }
+            }
+            // Every tile and layer gets its own --dots dots
+            let triangles = tile.map_or(1, |[columns, rows]| u64::from(columns) * u64::from(rows))
+                * u64::from(layers.unwrap_or(1));
+            check_dots(
+                width,
+                height,
+                dots.saturating_mul(triangles),
+                dot_size,
+                args.yes,
+            );
+            if repeat > 1 {
+                if save.output.as_deref() == Some("-") {
+                    error!("Can't write more than one image to stdout, so --repeat can't be used with --output -");
+                    process::exit(1);
+                }
+                // The dry run shows the name of the first image
+                save.index = Some(0);
+            }
+
+            // With --supersample, everything from here on is made at the larger size
+            let (saved_width, saved_height, saved_dots) = (width, height, dots);
+            let (width, height, dots, positions, start) = if supersample > 1 {
+                let (width, height) = match (
+                    width.checked_mul(supersample),
+                    height.checked_mul(supersample),
+                ) {
+                    (Some(width), Some(height)) => (width, height),
+                    _ => {
+                        error!(
+                            "A {width}x{height} image is too large to supersample by {supersample}"
+                        );
+                        process::exit(1);
+                    }
+                };
+                check_dimensions(
+                    width,
+                    height,
+                    channels * u128::from(depth / 8),
+                    args.max_memory,
+                );
+                let dots = dots.saturating_mul(u64::from(supersample).pow(2));
+                info!("Supersampling at {width}x{height} with {dots} dots");
+                save.supersample = supersample;
+
+                // Points move to the middle of the pixels they cover at the larger size
+                let scale = |[x, y]: [u32; 2]| {
+                    [
+                        x * supersample + supersample / 2,
+                        y * supersample + supersample / 2,
+                    ]
+                };
+                let positions =
+                    positions.map(|positions| positions.into_iter().map(scale).collect());
+                (width, height, dots, positions, start.map(scale))
+            } else {
+                (width, height, dots, positions, start)
+            };
+            let base = base.map(|base| match base.dimensions() {
+                size if size == (width, height) => base,
+                _ => base.resize_exact(width, height, FilterType::Triangle),
+            });
+
+            let (from, to) = match (&gradient, &radial) {
+                (Some(hex), _) | (None, Some(hex)) => (
+                    parse_color(Some(&hex[0]), args.strict),
+                    parse_color(Some(&hex[1]), args.strict),
+                ),
+                // The dots are colored by the palette instead, so there's no need to log about white
+                _ if palette.is_some()
+                    || palette_file.is_some()
+                    || color_source.is_some()
+                    || hue_map =>
+                {
+                    (Rgba([255, 255, 255, 255]), Rgba([255, 255, 255, 255]))
+                }
+                _ => {
+                    let col = parse_color(color.as_deref(), args.strict);
+                    (col, col)
+                }
+            };
+            let palette: Vec<Rgba<u8>> = palette
+                .iter()
+                .flatten()
+                .map(|hex| parse_color(Some(hex), args.strict))
+                .collect();
+            // The colors of the file are used like --palette, or cycled through dot by dot
+            let (palette, cycle) = match &palette_file {
+                Some(path) => {
+                    let colors = palette::read(path).unwrap_or_else(|err| {
+                        error!("{err}");
+                        process::exit(1);
+                    });
+                    if by_vertex {
+                        (colors, Vec::new())
+                    } else {
+                        (palette, colors)
+                    }
+                }
+                None => (palette, Vec::new()),
+            };
+            let corner = corner_color.map(|hex| parse_color(Some(&hex), args.strict));
+            let source = color_source
+                .as_deref()
+                .map(|path| read_color_source(path, width, height));
+
+            // The seed has to be known to be saved, so draw it here instead
+            let seed = seed.or_else(|| Some(rand::random()));
+            let options = Options {
+                seed,
+                vertices: vertices as usize,
+                positions,
+                apex,
+                restrict,
+                weights: weights.clone(),
+                ratio,
+                skip,
+                dot_size,
+                jitter,
+                generator: rng,
+                threads: threads as usize,
+                progress,
+                corners: !no_corners,
+                corner_size,
+                corner_shape,
+                start,
+                margin,
+                stop: Some(handle_interrupts()),
+                mask: mask.map(|path| Arc::new(read_mask(&path, width, height))),
+                hits: None,
+                stuck: None,
+            };
+            // With --radial the dots fade by how far they are from the center of the vertices,
+            // relative to the farthest vertex, instead of by when they're placed
+            let center = radial.is_some().then(|| {
+                let positions = options.positions(width, height);
+                let count = positions.len() as f64;
+                let (cx, cy) = positions.iter().fold((0.0, 0.0), |(cx, cy), &[x, y]| {
+                    (cx + x as f64 / count, cy + y as f64 / count)
+                });
+                let max = positions
+                    .iter()
+                    .map(|&[x, y]| (x as f64 - cx).hypot(y as f64 - cy))
+                    .fold(1.0, f64::max);
+                (cx, cy, max)
+            });
+            // With --hue-map the dots are colored by where they are in the box around the vertices
+            let hues = hue_map.then(|| {
+                let positions = options.positions(width, height);
+                let (xs, ys): (Vec<_>, Vec<_>) = positions.iter().map(|&[x, y]| (x, y)).unzip();
+                let min = [xs.iter().min(), ys.iter().min()].map(|c| *c.unwrap_or(&0) as f64);
+                let max = [xs.iter().max(), ys.iter().max()].map(|c| *c.unwrap_or(&0) as f64);
+                (min, max)
+            });
+            let sampled = |x: u32, y: u32| match hues {
+                Some((min, max)) => {
+                    let fraction = |c: u32, axis: usize| {
+                        ((c as f64 - min[axis]) / (max[axis] - min[axis]).max(1.0)).clamp(0.0, 1.0)
+                    };
+                    Some(hsv_to_rgb(fraction(x, 0) * HUE_RANGE, fraction(y, 1), 1.0))
+                }
+                None => source.as_ref().map(|img| *img.get_pixel(x, y)),
+            };
+            let t = move |x: u32, y: u32, i: u64| match center {
+                Some((cx, cy, max)) => ((x as f64 - cx).hypot(y as f64 - cy) / max).min(1.0),
+                None => i as f64 / dots.max(1) as f64,
+            };
+            let render = match mode {
+                Mode::Tetrahedron => {
+                    if animate.is_some() {
+                        warn!("Tetrahedrons can't be animated, ignoring --animate");
+                    }
+                    if heatmap {
+                        warn!("Tetrahedrons can't be made as a heatmap, ignoring --heatmap");
+                    }
+                    if tile.is_some() {
+                        warn!("Tetrahedrons can't be tiled, ignoring --tile");
+                    }
+                    if threads > 1 {
+                        warn!(
+                            "Tetrahedrons are always made on a single thread, ignoring --threads"
+                        );
+                    }
+                    if vertices != 3 || options.positions.is_some() {
+                        warn!(
+                            "A tetrahedron always has 4 vertices, ignoring --vertices and --vertex"
+                        );
+                    }
+                    if apex != Apex::Top {
+                        warn!("A tetrahedron always points up, ignoring --apex");
+                    }
+                    if jitter > 0 {
+                        warn!("The dots of a tetrahedron can't be jittered, ignoring --jitter");
+                    }
+                    if rng != Generator::Standard {
+                        warn!("Tetrahedrons are always made with the standard generator, ignoring --rng");
+                    }
+                    Render::Tetrahedron { rotate, shade }
+                }
+                Mode::Triangle if heatmap => {
+                    if animate.is_some() {
+                        warn!("Heatmaps can't be animated, ignoring --animate");
+                    }
+                    if color.is_some()
+                        || gradient.is_some()
+                        || radial.is_some()
+                        || !cycle.is_empty()
+                        || source.is_some()
+                        || hue_map
+                    {
+                        warn!(
+                            "Heatmaps are colored by --colormap, ignoring --color, --gradient, --radial, --palette-file, --color-source and --hue-map"
+                        );
+                    }
+                    if tile.is_some() {
+                        warn!("Heatmaps can't be tiled, ignoring --tile");
+                    }
+                    Render::Heatmap
+                }
+                Mode::Triangle if tile.is_some() => {
+                    let grid = tile.unwrap();
+                    check_tiles(grid, width, height);
+                    if animate.is_some() {
+                        warn!("Tiles can't be animated, ignoring --animate");
+                    }
+                    if options.positions.is_some() {
+                        warn!(
+                            "The vertices are placed in every tile, ignoring --vertex and --rotate"
+                        );
+                    }
+                    Render::Tiles {
+                        grid,
+                        flip: flip_tiles,
+                    }
+                }
+                Mode::Triangle if layers.is_some() => {
+                    if animate.is_some() {
+                        warn!("Layers can't be animated, ignoring --animate");
+                    }
+                    if antialias {
+                        warn!("Layers can't be antialiased, ignoring --antialias");
+                    }
+                    Render::Layers {
+                        layers: layers.unwrap(),
+                        palette: !palette.is_empty(),
+                    }
+                }
+                Mode::Triangle => {
+                    match animate {
+                        Some(frames) => {
+                            if threads > 1 {
+                                warn!(
+                                "Animations are always made on a single thread, ignoring --threads"
+                            );
+                            }
+                            Render::Animation(frames)
+                        }
+                        None if antialias => {
+                            if threads > 1 {
+                                warn!("Antialiased images are always made on a single thread, ignoring --threads");
+                            }
+                            if dot_size > 1 {
+                                warn!("Antialiased dots are always a single pixel, ignoring --dot-size");
+                            }
+                            if jitter > 0 {
+                                warn!("Antialiased dots can't be jittered, ignoring --jitter");
+                            }
+                            if rng != Generator::Standard {
+                                warn!("Antialiased images are always made with the standard generator, ignoring --rng");
+                            }
+                            Render::Antialiased
+                        }
+                        None if !palette.is_empty() => {
+                            let count = options.positions(width, height).len();
+                            if palette.len() != count && !by_vertex {
+                                error!(
+                                    "Expected {count} colors in --palette, one for every vertex, but {} were given",
+                                    palette.len()
+                                );
+                                process::exit(1);
+                            }
+                            if threads > 1 {
+                                warn!("Palettes are always made on a single thread, ignoring --threads");
+                            }
+                            if antialias {
+                                warn!("Palettes can't be antialiased, ignoring --antialias");
+                            }
+                            Render::Palette
+                        }
+                        None => match checkpoint {
+                            Some(every) => {
+                                if threads > 1 {
+                                    warn!("Checkpoints are always made on a single thread, ignoring --threads");
+                                }
+                                Render::Checkpoints(every)
+                            }
+                            None if crop => {
+                                if threads > 1 {
+                                    warn!("Cropped images are always made on a single thread, ignoring --threads");
+                                }
+                                Render::Cropped
+                            }
+                            None => Render::Image,
+                        },
+                    }
+                }
+            };
+            let render = match (spin, render) {
+                (None, render) => render,
+                (Some(seconds), Render::Image) => Render::Spin {
+                    frames: spin_frames(seconds),
+                    tetrahedron: false,
+                    rotate: 0.0,
+                    shade: false,
+                },
+                (Some(seconds), Render::Tetrahedron { rotate, shade }) => Render::Spin {
+                    frames: spin_frames(seconds),
+                    tetrahedron: true,
+                    rotate,
+                    shade,
+                },
+                (Some(_), render) => {
+                    warn!("Only plain single images and tetrahedrons can spin, ignoring --spin");
+                    render
+                }
+            };
+            if let Render::Spin { frames, .. } = render {
+                let frame = width as u128 * height as u128 * channels * u128::from(depth / 8);
+                let memory = frame * frames as u128;
+                if memory > SPIN_MEMORY {
+                    warn!(
+                        "The {frames} frames of the spin take about {} of memory, and the saved file can get as large",
+                        format_size(memory)
+                    );
+                }
+                if threads > 1 {
+                    warn!("Spins are always made on a single thread, ignoring --threads");
+                }
+            }
+            if frames_dir.is_some() && !matches!(render, Render::Animation(_) | Render::Spin { .. })
+            {
+                warn!("Only animations have frames, ignoring --frames-dir");
+            }
+            save.frames_dir = frames_dir;
+            if checkpoint.is_some() && !matches!(render, Render::Checkpoints(_)) {
+                warn!("Only plain single images can have checkpoints, ignoring --checkpoint");
+            }
+            if crop && !matches!(render, Render::Cropped) {
+                warn!("Only plain single images can be cropped, ignoring --crop");
+            }
+            if layers.is_some() && !matches!(render, Render::Layers { .. }) {
+                warn!("Only flat polygons can be layered, ignoring --layers");
+            }
+            if !palette.is_empty() && !matches!(render, Render::Palette | Render::Layers { .. }) {
+                warn!("Only single images of flat polygons can use a palette, ignoring --palette");
+            }
+            let flat = !matches!(
+                render,
+                Render::Tetrahedron { .. }
+                    | Render::Spin {
+                        tetrahedron: true,
+                        ..
+                    }
+            );
+            if options.mask.is_some()
+                && (!flat || matches!(render, Render::Antialiased | Render::Tiles { .. }))
+            {
+                warn!("Only flat polygons that aren't antialiased or tiled can be masked, ignoring --mask");
+            }
+            // The other renders don't place their dots like make_points does
+            let countable = matches!(
+                render,
+                Render::Image
+                    | Render::Cropped
+                    | Render::Checkpoints(_)
+                    | Render::Animation(_)
+                    | Render::Heatmap
+                    | Render::Palette
+            );
+            if stats && !countable {
+                warn!("Only single images of flat polygons can count their hits, ignoring --stats");
+            }
+            if antialias && !matches!(render, Render::Antialiased | Render::Palette) {
+                warn!("Only single images can be antialiased, ignoring --antialias");
+            }
+
+            let colors = match render {
+                Render::Heatmap => format!(
+                    "The {} colormap",
+                    colormap.to_possible_value().unwrap().get_name()
+                ),
+                Render::Palette | Render::Layers { palette: true, .. } => palette
+                    .iter()
+                    .map(|&col| to_hex(col))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                _ if hue_map => "Hues by where the dots are".to_string(),
+                _ if source.is_some() => {
+                    format!(
+                        "The colors of {}",
+                        color_source.as_deref().unwrap_or_default()
+                    )
+                }
+                _ if !cycle.is_empty() => format!(
+                    "The {} colors of {} in turn",
+                    cycle.len(),
+                    palette_file.as_deref().unwrap_or_default()
+                ),
+                _ if radial.is_some() => format!(
+                    "{} in the center to {} at the vertices",
+                    to_hex(from),
+                    to_hex(to)
+                ),
+                _ if from == to => to_hex(from),
+                _ => format!("{} to {}", to_hex(from), to_hex(to)),
+            };
+            save.settings = Some(Metadata {
+                version: env!("CARGO_PKG_VERSION"),
+                width: saved_width,
+                height: saved_height,
+                dots: Some(saved_dots),
+                iterations: None,
+                color: colors.clone(),
+                seed,
+                restriction: Some(restrict.to_possible_value().unwrap().get_name().to_string()),
+                ratio: Some(ratio),
+                weights: weights.clone(),
+                supersample: (supersample > 1).then_some(supersample),
+                // Only the fast generator is saved, so images made before it can be made again
+                rng: (rng != Generator::Standard)
+                    .then(|| rng.to_possible_value().unwrap().get_name().to_string()),
+                vertices: Some(vertices),
+            });
+
+            if save.dry_run {
+                let vertices = match render {
+                    Render::Tetrahedron { rotate, .. } => {
+                        format!("The 4 corners of a tetrahedron turned {rotate} degrees")
+                    }
+                    Render::Spin {
+                        frames,
+                        tetrahedron: true,
+                        rotate,
+                        ..
+                    } => format!(
+                        "The 4 corners of a tetrahedron turning a full circle from {rotate} degrees in {frames} frames"
+                    ),
+                    Render::Spin { frames, .. } => format!(
+                        "{} turning a full circle in {frames} frames",
+                        format_positions(&spin_positions(
+                            &options.positions(width, height),
+                            width,
+                            height
+                        ))
+                    ),
+                    Render::Tiles {
+                        grid: [cols, rows], ..
+                    } => format!(
+                        "{} in each of the {cols}x{rows} tiles",
+                        format_positions(&options.layout(width / cols, height / rows))
+                    ),
+                    _ => format_positions(&options.positions(width, height)),
+                };
+                let background = match background {
+                    _ if base.is_some() => "The --base image".to_string(),
+                    _ if transparent => "Transparent".to_string(),
+                    Some(hex) => to_hex(parse_color(Some(&hex), args.strict)),
+                    None => to_hex(Rgba([0, 0, 0, 255])),
+                };
+                let background = if bg_noise && !transparent && base.is_none() {
+                    format!("{background} with noise")
+                } else {
+                    background
+                };
+
+                let pixels = width as u128 * height as u128;
+                let image = pixels * channels * u128::from(depth / 8);
+                let threads = threads as u128;
+                let memory = match render {
+                    Render::Animation(frames) | Render::Spin { frames, .. } => {
+                        image * (frames as u128 + 1)
+                    }
+                    Render::Heatmap => image + pixels * 4 * threads,
+                    Render::Image if threads > 1 => image + pixels * 2 * threads,
+                    _ => image,
+                };
+
+                let animation = matches!(render, Render::Animation(_) | Render::Spin { .. });
+                print_plan(&[
+                    (
+                        "Size",
+                        match supersample {
+                            1 => format!("{width}x{height}"),
+                            _ => format!("{saved_width}x{saved_height} (Made at {width}x{height})"),
+                        },
+                    ),
+                    ("Dots", dots.to_string()),
+                    ("Vertices", vertices),
+                    ("Color", colors),
+                    ("Background", background),
+                    ("Memory", format_size(memory)),
+                    ("Output", {
+                        if ascii {
+                            "Text in the terminal".to_string()
+                        } else if let (Some(dir), None, true) =
+                            (&save.frames_dir, &save.output, animation)
+                        {
+                            format!("Numbered frames in {}", dir.display())
+                        } else {
+                            let (width, height) = scaled(saved_width, saved_height, &save);
+                            output_path(width, height, dots, animation, &save)
+                        }
+                    }),
+                ]);
+                return;
+            }
+
+            if let Some(path) = &export_points {
+                if supersample > 1 {
+                    warn!("The points of supersampled images aren't on the saved image, ignoring --export-points");
+                } else if matches!(render, Render::Image | Render::Checkpoints(_)) {
+                    write_points(path, width, height, dots, &options, save.force);
+                } else {
+                    warn!("Only single images of flat polygons can export their points, ignoring --export-points");
+                }
+                if save.output.is_none() && !save.wallpaper {
+                    info!("Finished in {}ms", start_time.elapsed().as_millis());
+                    return;
+                }
+            }
+
+            let flat = base.is_none() && (!bg_noise || transparent);
+            let measure_dimension = if !flat && measure_dimension {
+                warn!("The dots can't be told apart from a background that isn't flat, ignoring --measure-dimension");
+                false
+            } else {
+                measure_dimension
+            };
+            if ascii {
+                if !flat {
+                    warn!("The dots can't be told apart from a background that isn't flat, ignoring --ascii");
+                } else {
+                    if save.output.is_some() || save.wallpaper {
+                        warn!("The image is printed as text, ignoring --output and --wallpaper");
+                    }
+                    save.ascii = true;
+                }
+            }
+            for n in 0..repeat {
+                // Every image gets the next seed, so each of them can be made again on its own
+                let options = Options {
+                    seed: seed.map(|seed| seed.wrapping_add(n.into())),
+                    hits: (stats && countable).then(|| Arc::new(HitCounts::new(width, height))),
+                    stuck: options.mask.is_some().then(Arc::default),
+                    ..options.clone()
+                };
+                if repeat > 1 {
+                    info!("Making image {} of {repeat}", n + 1);
+                    save.index = Some(n);
+                    if let Some(settings) = &mut save.settings {
+                        settings.seed = options.seed;
+                    }
+                }
+
+                if transparent {
+                    if background.is_some() {
+                        warn!("The background is transparent, ignoring --background");
+                    }
+                    if bg_noise {
+                        warn!("The background is transparent, ignoring --bg-noise");
+                    }
+                    if depth == 16 {
+                        let wide = |Rgba(col): Rgba<u8>| Rgba(col.map(widen));
+                        let (from, to, corner) = (wide(from), wide(to), corner.map(wide));
+                        let cycle: Vec<_> = cycle.iter().copied().map(wide).collect();
+                        let img = base.as_ref().map_or_else(
+                            || ImageBuffer::<Rgba<u16>, _>::new(width, height),
+                            DynamicImage::to_rgba16,
+                        );
+                        let col = |x, y, i| match (corner, sampled(x, y)) {
+                            (Some(col), _) if i == 0 => col,
+                            (_, Some(col)) => wide(col),
+                            _ if !cycle.is_empty() => cycle[(i % cycle.len() as u64) as usize],
+                            _ => interpolate(from, to, t(x, y, i)),
+                        };
+                        let heat = |t| colormap.color16(t).to_rgba();
+                        let vertex = |n: usize| wide(palette[n % palette.len()]);
+                        generate(
+                            img,
+                            dots,
+                            &options,
+                            render,
+                            col,
+                            heat,
+                            vertex,
+                            &save,
+                            measure_dimension,
+                        );
+                    } else {
+                        let img = base
+                            .as_ref()
+                            .map_or_else(|| RgbaImage::new(width, height), DynamicImage::to_rgba8);
+                        let col = |x, y, i| match (corner, sampled(x, y)) {
+                            (Some(col), _) if i == 0 => col,
+                            (_, Some(col)) => col,
+                            _ if !cycle.is_empty() => cycle[(i % cycle.len() as u64) as usize],
+                            _ => interpolate(from, to, t(x, y, i)),
+                        };
+                        let heat = |t| colormap.color(t).to_rgba();
+                        let vertex = |n: usize| palette[n % palette.len()];
+                        generate(
+                            img,
+                            dots,
+                            &options,
+                            render,
+                            col,
+                            heat,
+                            vertex,
+                            &save,
+                            measure_dimension,
+                        );
+                    }
+                } else {
+                    let background = match &background {
+                        Some(hex) => to_rgb(parse_color(Some(hex), args.strict)),
+                        None => Rgb([0, 0, 0]),
+                    };
+                    let (from, to) = match to_rgb(from) {
+                        rgb if from == to => (rgb, rgb),
+                        rgb => (rgb, to_rgb(to)),
+                    };
+                    let corner = corner.map(to_rgb);
+                    let cycle: Vec<_> = cycle.iter().map(|col| col.to_rgb()).collect();
+                    let seed = options.seed.unwrap_or_default();
+                    let backdrop = |x, y| {
+                        if !bg_noise {
+                            return background;
+                        }
+                        let noise = value_noise(x, y, width, height, seed) * 2.0 - 1.0;
+                        Rgb(background.0.map(|c| {
+                            (c as f64 + noise * NOISE_AMOUNT).round().clamp(0.0, 255.0) as u8
+                        }))
+                    };
+                    if depth == 16 {
+                        let wide = |Rgb(col): Rgb<u8>| Rgb(col.map(widen));
+                        let (from, to, corner) = (wide(from), wide(to), corner.map(wide));
+                        let cycle: Vec<_> = cycle.into_iter().map(wide).collect();
+                        let img = base.as_ref().map_or_else(
+                            || ImageBuffer::from_fn(width, height, |x, y| wide(backdrop(x, y))),
+                            DynamicImage::to_rgb16,
+                        );
+                        let col = |x, y, i| match (corner, sampled(x, y)) {
+                            (Some(col), _) if i == 0 => col,
+                            (_, Some(col)) => wide(to_rgb(col)),
+                            _ if !cycle.is_empty() => cycle[(i % cycle.len() as u64) as usize],
+                            _ => interpolate(from, to, t(x, y, i)),
+                        };
+                        let heat = |t| colormap.color16(t);
+                        let vertex = |n: usize| wide(to_rgb(palette[n % palette.len()]));
+                        generate(
+                            img,
+                            dots,
+                            &options,
+                            render,
+                            col,
+                            heat,
+                            vertex,
+                            &save,
+                            measure_dimension,
+                        );
+                    } else {
+                        let img = base.as_ref().map_or_else(
+                            || RgbImage::from_fn(width, height, backdrop),
+                            DynamicImage::to_rgb8,
+                        );
+                        let col = |x, y, i| match (corner, sampled(x, y)) {
+                            (Some(col), _) if i == 0 => col,
+                            (_, Some(col)) => to_rgb(col),
+                            _ if !cycle.is_empty() => cycle[(i % cycle.len() as u64) as usize],
+                            _ => interpolate(from, to, t(x, y, i)),
+                        };
+                        let heat = |t| colormap.color(t);
+                        let vertex = |n: usize| to_rgb(palette[n % palette.len()]);
+                        generate(
+                            img,
+                            dots,
+                            &options,
+                            render,
+                            col,
+                            heat,
+                            vertex,
+                            &save,
+                            measure_dimension,
+                        );
+                    }
+                }
+
+                if let Some(hits) = &options.hits {
+                    log_stats(hits);
+                }
+                if options.stopped() {
+                    break;
+                }
+            }
+        }
+        Commands::Image {
+            image,
+            dots,
+            color,
+            no_corners,
+            corner_color,
+            darken,
+            grayscale: _,
+            no_grayscale,
+            mut save,
+            seed,
+            skip,
+            dot_size,
+            threads,
+        } => {
+            let name = match image.as_str() {
+                "-" => "stdin",
+                path => path,
+            };
+            if save.dry_run {
+                let (width, height) = read_dimensions(&image).unwrap_or_else(|err| {
+                    error!("Couldn't read file {name}: {err}");
+                    process::exit(1);
+                });
+                check_image_size(name, width, height);
+                check_dots(width, height, dots, dot_size, args.yes);
+                let colors = match &color {
+                    Some(hex) => to_hex(parse_color(Some(hex), args.strict)),
+                    None => format!("The colors of {name}"),
+                };
+                let pixels = width as u128 * height as u128;
+                let memory = pixels * 3
+                    + if threads > 1 {
+                        pixels * 2 * threads as u128
+                    } else {
+                        0
+                    };
+
+                print_plan(&[
+                    ("Size", format!("{width}x{height}")),
+                    ("Dots", dots.to_string()),
+                    (
+                        "Vertices",
+                        format_positions(&Options::default().positions(width, height)),
+                    ),
+                    ("Color", colors),
+                    ("Memory", format_size(memory)),
+                    ("Output", {
+                        let (width, height) = scaled(width, height, &save);
+                        output_path(width, height, dots, false, &save)
+                    }),
+                ]);
+                return;
+            }
+
+            info!("Reading {name}");
+            let im = read_rgb_image(&image).unwrap_or_else(|err| {
+                error!("Couldn't read file {name}: {err}");
+                process::exit(1);
+            });
+            check_image_size(name, im.width(), im.height());
+            check_dots(im.width(), im.height(), dots, dot_size, args.yes);
+
+            let seed = seed.or_else(|| Some(rand::random()));
+            let options = Options {
+                seed,
+                skip,
+                dot_size,
+                threads: threads as usize,
+                progress,
+                corners: !no_corners,
+                stop: Some(handle_interrupts()),
+                ..Options::default()
+            };
+            let color = color.map(|hex| to_rgb(parse_color(Some(&hex), args.strict)));
+            let corner = corner_color.map(|hex| to_rgb(parse_color(Some(&hex), args.strict)));
+            save.settings = Some(Metadata {
+                version: env!("CARGO_PKG_VERSION"),
+                width: im.width(),
+                height: im.height(),
+                dots: Some(dots),
+                iterations: None,
+                color: match color {
+                    Some(col) => to_hex(col.to_rgba()),
+                    None => format!("The colors of {name}"),
+                },
+                seed,
+                restriction: None,
+                ratio: None,
+                weights: None,
+                supersample: None,
+                rng: None,
+                vertices: None,
+            });
+            let backdrop = image_backdrop(&im, darken, !no_grayscale);
+            let img = make_image(backdrop, dots, &options, |x, y, i| match (corner, color) {
+                (Some(col), _) if i == 0 => col,
+                (_, Some(col)) => col,
+                _ => *im.get_pixel(x, y),
+            });
+
+            handle_image(img.into(), dots, &save);
+        }
+        Commands::Fern {
+            width,
+            height,
+            dots,
+            color,
+            background,
+            mut save,
+            seed,
+            skip,
+            dot_size,
+        } => {
+            check_dimensions(width, height, 3, args.max_memory);
+            check_dots(width, height, dots, dot_size, args.yes);
+
+            let color = to_rgb(parse_color(color.as_deref(), args.strict));
+            let background = match background {
+                Some(hex) => to_rgb(parse_color(Some(&hex), args.strict)),
+                None => Rgb([0, 0, 0]),
+            };
+            let seed = seed.or_else(|| Some(rand::random()));
+            let options = Options {
+                seed,
+                skip,
+                dot_size,
+                progress,
+                stop: Some(handle_interrupts()),
+                ..Options::default()
+            };
+            save.settings = Some(Metadata {
+                version: env!("CARGO_PKG_VERSION"),
+                width,
+                height,
+                dots: Some(dots),
+                iterations: None,
+                color: to_hex(color.to_rgba()),
+                seed,
+                restriction: None,
+                ratio: None,
+                weights: None,
+                supersample: None,
+                rng: None,
+                vertices: None,
+            });
+
+            if save.dry_run {
+                print_plan(&[
+                    ("Size", format!("{width}x{height}")),
+                    ("Dots", dots.to_string()),
+                    ("Color", to_hex(color.to_rgba())),
+                    ("Background", to_hex(background.to_rgba())),
+                    ("Memory", format_size(width as u128 * height as u128 * 3)),
+                    ("Output", {
+                        let (width, height) = scaled(width, height, &save);
+                        output_path(width, height, dots, false, &save)
+                    }),
+                ]);
+                return;
+            }
+
+            let img = RgbImage::from_pixel(width, height, background);
+            let img = make_fern(img, dots, &options, |_, _, _| color);
+            handle_image(img.into(), dots, &save);
+        }
+        Commands::Ifs {
+            config,
+            width,
+            height,
+            dots,
+            color,
+            background,
+            mut save,
+            seed,
+            skip,
+            dot_size,
+        } => {
+            check_dimensions(width, height, 3, args.max_memory);
+            check_dots(width, height, dots, dot_size, args.yes);
+            let maps = read_maps(&config).unwrap_or_else(|err| {
+                error!("{err}");
+                process::exit(1);
+            });
+
+            let color = to_rgb(parse_color(color.as_deref(), args.strict));
+            let background = match background {
+                Some(hex) => to_rgb(parse_color(Some(&hex), args.strict)),
+                None => Rgb([0, 0, 0]),
+            };
+            let seed = seed.unwrap_or_else(rand::random);
+            let Some(bounds) = ifs_bounds(&maps, seed) else {
+                error!("The maps of {config} run off to infinity, so at least one of them doesn't shrink everything");
+                process::exit(1);
+            };
+            let options = Options {
+                seed: Some(seed),
+                skip,
+                dot_size,
+                progress,
+                stop: Some(handle_interrupts()),
+                ..Options::default()
+            };
+            save.settings = Some(Metadata {
+                version: env!("CARGO_PKG_VERSION"),
+                width,
+                height,
+                dots: Some(dots),
+                iterations: None,
+                color: to_hex(color.to_rgba()),
+                seed: Some(seed),
+                restriction: None,
+                ratio: None,
+                weights: None,
+                supersample: None,
+                rng: None,
+                vertices: None,
+            });
+
+            if save.dry_run {
+                print_plan(&[
+                    ("Size", format!("{width}x{height}")),
+                    ("Dots", dots.to_string()),
+                    ("Maps", maps.len().to_string()),
+                    ("Color", to_hex(color.to_rgba())),
+                    ("Background", to_hex(background.to_rgba())),
+                    ("Memory", format_size(width as u128 * height as u128 * 3)),
+                    ("Output", {
+                        let (width, height) = scaled(width, height, &save);
+                        output_path(width, height, dots, false, &save)
+                    }),
+                ]);
+                return;
+            }
+
+            let img = RgbImage::from_pixel(width, height, background);
+            let img = make_ifs(img, dots, &maps, bounds, &options, |_, _, _| color);
+            handle_image(img.into(), dots, &save);
+        }
+        Commands::Carpet {
+            width,
+            height,
+            iterations,
+            color,
+            background,
+            mut save,
+        } => {
+            check_dimensions(width, height, 3, args.max_memory);
+
+            let color = to_rgb(parse_color(color.as_deref(), args.strict));
+            let background = match background {
+                Some(hex) => to_rgb(parse_color(Some(&hex), args.strict)),
+                None => Rgb([0, 0, 0]),
+            };
+            save.settings = Some(Metadata {
+                version: env!("CARGO_PKG_VERSION"),
+                width,
+                height,
+                dots: None,
+                iterations: Some(iterations),
+                color: to_hex(color.to_rgba()),
+                seed: None,
+                restriction: None,
+                ratio: None,
+                weights: None,
+                supersample: None,
+                rng: None,
+                vertices: None,
+            });
+
+            // The iterations take the place of the dots in the default file name
+            if save.dry_run {
+                print_plan(&[
+                    ("Size", format!("{width}x{height}")),
+                    ("Iterations", iterations.to_string()),
+                    ("Color", to_hex(color.to_rgba())),
+                    ("Background", to_hex(background.to_rgba())),
+                    ("Memory", format_size(width as u128 * height as u128 * 3)),
+                    ("Output", {
+                        let (width, height) = scaled(width, height, &save);
+                        output_path(width, height, iterations.into(), false, &save)
+                    }),
+                ]);
+                return;
+            }
+
+            let img = RgbImage::from_pixel(width, height, background);
+            let img = make_carpet(img, iterations, color);
+            handle_image(img.into(), iterations.into(), &save);
+        }
+        Commands::Interactive {
+            width,
+            height,
+            dots,
+            color,
+            mut save,
+            seed,
+        } => {
+            check_dimensions(width, height, 3, args.max_memory);
+            if save.dry_run {
+                warn!("The preview doesn't make anything until it's saved, ignoring --dry-run");
+            }
+
+            let settings = interactive::Settings {
+                dots,
+                color: to_rgb(parse_color(color.as_deref(), args.strict)),
+                seed: seed.unwrap_or_else(rand::random),
+            };
+            let term = Term::stdout();
+            let result = interactive::run(&term, width, height, settings, |settings| {
+                let interactive::Settings { dots, color, seed } = settings;
+                save.settings = Some(Metadata {
+                    version: env!("CARGO_PKG_VERSION"),
+                    width,
+                    height,
+                    dots: Some(dots),
+                    iterations: None,
+                    color: to_hex(color.to_rgba()),
+                    seed: Some(seed),
+                    restriction: None,
+                    ratio: None,
+                    weights: None,
+                    supersample: None,
+                    rng: None,
+                    vertices: None,
+                });
+                let options = Options {
+                    seed: Some(seed),
+                    progress,
+                    ..Options::default()
+                };
+                let img = make_image(RgbImage::new(width, height), dots, &options, |_, _, _| {
+                    color
+                });
+                handle_image(img.into(), dots, &save);
+            });
+            result.unwrap_or_else(|err| {
+                error!("Couldn't show the preview: {err}");
+                process::exit(1);
+            });
+        }
+        Commands::Info { file } => {
+            let settings = read_metadata(&file).unwrap_or_else(|err| {
+                error!("{err}");
+                process::exit(1);
+            });
+            if settings.is_empty() {
+                error!("{file} has no settings saved in it");
+                process::exit(1);
+            }
+
+            let plan: Vec<_> = settings
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.clone()))
+                .collect();
+            print_plan(&plan);
+        }
+        Commands::Verify { file } => {
+            let settings = read_metadata(&file).unwrap_or_else(|err| {
+                error!("{err}");
+                process::exit(1);
+            });
+            let img = image::open(&file).unwrap_or_else(|err| {
+                error!("Couldn't open {file}: {err}");
+                process::exit(1);
+            });
+            let made =
+                remake_image(&settings, img.color().has_alpha(), progress).unwrap_or_else(|err| {
+                    error!("{file} can't be verified: {err}");
+                    process::exit(1);
+                });
+
+            if made.dimensions() != img.dimensions() {
+                println!(
+                    "FAIL {file}: It's {}x{}, but its settings make a {}x{} image",
+                    img.width(),
+                    img.height(),
+                    made.width(),
+                    made.height()
+                );
+                process::exit(1);
+            }
+            // Comparing 16 bit pixels, as 8 bit colors are widened the same way as when saving
+            let differing = made
+                .to_rgba16()
+                .pixels()
+                .zip(img.to_rgba16().pixels())
+                .filter(|(made, saved)| made != saved)
+                .count();
+            if differing > 0 {
+                println!(
+                    "FAIL {file}: {differing} of {} pixels differ from the image its settings make",
+                    u64::from(img.width()) * u64::from(img.height())
+                );
+                process::exit(1);
+            }
+            println!("PASS {file}");
+        }
+        Commands::Completions { shell } => {
+            let mut command = Cli::command();
+            let name = command.get_name().to_string();
+            clap_complete::generate(shell, &mut command, name, &mut io::stdout());
+        }
+        Commands::Batch { file, force } => {
+            let jobs = batch::read_jobs(&file).unwrap_or_else(|err| {
+                error!("{err}");
+                process::exit(1);
+            });
+
+            let failed = batch::run(&jobs, args.strict, force, args.max_memory, args.yes);
+            if failed > 0 {
+                error!("{failed} of {} jobs failed", jobs.len());
+                process::exit(1);
+            }
+        }
+    }
+
+    info!("Finished in {}ms", start_time.elapsed().as_millis());
+}
+
+/// Open the image at `path`, downloading it first when it's an http:// or https:// URL, or reading
+/// it from stdin when it's -
+fn read_image(path: &str) -> Result<DynamicImage, String> {
+    if path == "-" {
+        let bytes = read_stdin()?;
+        return image::load_from_memory(&bytes).map_err(|err| err.to_string());
+    }
+    if !is_url(path) {
+        return image::open(path).map_err(|err| err.to_string());
+    }
+
+    info!("Downloading {path}");
+    let bytes = download(path)?;
+    image::load_from_memory(&bytes).map_err(|err| err.to_string())
+}
+
+/// Read the image at `path` like [`read_image`] as 8 bit RGB, whatever its color type
+///
+/// The dots are colored and the image is darkened on this, so 16 bit, grayscale and palette images
+/// are handled like any other instead of being darkened on their own scale. Transparency is dropped.
+fn read_rgb_image(path: &str) -> Result<RgbImage, String> {
+    read_image(path).map(DynamicImage::into_rgb8)
+}
+
+/// The image the dots of the image subcommand are drawn on, brightened by `darken` and turned
+/// gray if `grayscale` is on
+fn image_backdrop(im: &RgbImage, darken: i32, grayscale: bool) -> RgbImage {
+    let im = DynamicImage::ImageRgb8(im.clone());
+    let im = if grayscale { im.grayscale() } else { im };
+    im.brighten(darken).into_rgb8()
+}
+
+/// The width and height of the image at `path` (Or - for stdin, or a URL like [`read_image`]),
+/// reading only its header when it's a file
+fn read_dimensions(path: &str) -> Result<(u32, u32), String> {
+    let bytes = if path == "-" {
+        read_stdin()?
+    } else if is_url(path) {
+        info!("Downloading {path}");
+        download(path)?
+    } else {
+        return image::image_dimensions(path).map_err(|err| err.to_string());
+    };
+
+    image::io::Reader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|err| err.to_string())?
+        .into_dimensions()
+        .map_err(|err| err.to_string())
+}
+
+/// Read all of stdin into memory
+fn read_stdin() -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    io::stdin()
+        .lock()
+        .read_to_end(&mut bytes)
+        .map_err(|err| err.to_string())?;
+    if bytes.is_empty() {
+        return Err("Nothing was piped to stdin".to_string());
+    }
+
+    Ok(bytes)
+}
+
+fn is_url(path: &str) -> bool {
+    ["http://", "https://"].iter().any(|scheme| {
+        path.get(..scheme.len())
+            .is_some_and(|s| s.eq_ignore_ascii_case(scheme))
+    })
+}
+
+/// Download `url` into memory
+fn download(url: &str) -> Result<Vec<u8>, String> {
+    let response = ureq::get(url)
+        .call()
+        .map_err(|err| format!("Couldn't download it: {err}"))?;
+
+    let mut bytes = Vec::new();
+    response
+        .into_reader()
+        .read_to_end(&mut bytes)
+        .map_err(|err| format!("Couldn't download it: {err}"))?;
+
+    Ok(bytes)
+}
+
+/// Set when Ctrl-C is pressed while placing dots, so the ones placed so far are still saved
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Make Ctrl-C set [`INTERRUPTED`] instead of quitting, returning it to stop the dots with
+///
+/// Pressing Ctrl-C again quits right away, in case saving the image hangs.
+fn handle_interrupts() -> &'static AtomicBool {
+    static HANDLER: Once = Once::new();
+    HANDLER.call_once(|| {
+        let handler = ctrlc::set_handler(|| {
+            if INTERRUPTED.swap(true, Ordering::Relaxed) {
+                process::exit(130);
+            }
+        });
+        if let Err(err) = handler {
+            warn!("Couldn't catch Ctrl-C, so it will quit without saving: {err}");
+        }
+    });
+
+    &INTERRUPTED
+}
+
+/// Quit with an error when the dots got stuck off the mask of `options`, instead of saving the
+/// image they didn't finish
+fn exit_if_stuck(options: &Options) {
+    if options.is_stuck() {
+        error!("The dots got stuck where every jump leaves the mask, so the image wasn't saved");
+        process::exit(1);
+    }
+}
+
+/// Place the dots on `img` as given by `render` and save it
+///
+/// The dots are colored by `color`, except for heatmaps which are colored by `heat` at the density,
+/// and palettes which are colored by `vertex` at the index of the vertex (Or of the layer).
+#[allow(clippy::too_many_arguments)]
+fn generate<P, F, H, V>(
+    img: ImageBuffer<P, Vec<P::Subpixel>>,
     dots: u64,
-    output: Option<String>,
-    wallpaper: bool,
-) {
-    let save_path: String;
-    info!("Saving image");
-    if let Some(path) = output {
-        save_path = path;
+    options: &Options,
+    render: Render,
+    color: F,
+    heat: H,
+    vertex: V,
+    save: &Save,
+    measure: bool,
+) where
+    P: Pixel + PartialEq + 'static,
+    F: Fn(u32, u32, u64) -> P,
+    H: Fn(f64) -> P,
+    V: Fn(usize) -> P,
+    DynamicImage: From<ImageBuffer<P, Vec<P::Subpixel>>>,
+{
+    // Nothing is drawn yet, so any pixel is the background
+    let background = *img.get_pixel(0, 0);
+    let mut partial = None;
+    let img = match render {
+        Render::Image => make_image(img, dots, options, color),
+        Render::Cropped => make_cropped(img, dots, options, color),
+        Render::Checkpoints(every) => {
+            let shrink = save.supersample.max(1);
+            let (width, height) = scaled(img.width() / shrink, img.height() / shrink, save);
+            partial = partial_path(&output_path(width, height, dots, false, save));
+            let path = partial.as_deref();
+            make_checkpoints(img, dots, options, every, color, |i, img| {
+                if let Some(path) = path {
+                    save_checkpoint(DynamicImage::from(img.clone()), i, path, save);
+                }
+            })
+        }
+        Render::Animation(frames) => {
+            if measure {
+                warn!("The dimension of an animation isn't measured, ignoring --measure-dimension");
+            }
+            if save.ascii {
+                warn!("Animations can't be printed as text, ignoring --ascii");
+            }
+            let frames = make_frames(img, dots, options, frames, color);
+            exit_if_stuck(options);
+            handle_animation(
+                frames.into_iter().map(DynamicImage::from).collect(),
+                dots,
+                ANIMATION_DELAY,
+                save,
+            );
+            return;
+        }
+        Render::Spin {
+            frames,
+            tetrahedron,
+            rotate,
+            shade,
+        } => {
+            if measure {
+                warn!("The dimension of an animation isn't measured, ignoring --measure-dimension");
+            }
+            if save.ascii {
+                warn!("Animations can't be printed as text, ignoring --ascii");
+            }
+            let (width, height) = img.dimensions();
+            let positions = spin_positions(&options.positions(width, height), width, height);
+            let options = Options {
+                threads: 1,
+                ..options.clone()
+            };
+            let frames = (0..frames)
+                .map(|k| {
+                    info!("Making frame {} of {frames}", k + 1);
+                    let degrees = 360.0 * k as f64 / frames as f64;
+                    let frame = if tetrahedron {
+                        make_tetrahedron(
+                            img.clone(),
+                            dots,
+                            &options,
+                            rotate + degrees,
+                            shade,
+                            &color,
+                        )
+                    } else {
+                        let options = Options {
+                            positions: Some(rotate_positions(&positions, degrees, width, height)),
+                            ..options.clone()
+                        };
+                        make_image(img.clone(), dots, &options, &color)
+                    };
+                    DynamicImage::from(frame)
+                })
+                .collect();
+            exit_if_stuck(&options);
+            handle_animation(frames, dots, SPIN_DELAY, save);
+            return;
+        }
+        Render::Tetrahedron { rotate, shade } => {
+            make_tetrahedron(img, dots, options, rotate, shade, color)
+        }
+        Render::Heatmap => make_heatmap(img, dots, options, heat),
+        Render::Palette => make_palette(img, dots, options, vertex),
+        Render::Antialiased => make_antialiased(img, dots, options, color),
+        Render::Tiles { grid, flip } => make_tiles(img, dots, options, grid, flip, color),
+        Render::Layers { layers, palette } => {
+            make_layers(img, dots, options, layers, |k, x, y, i| {
+                if palette {
+                    vertex(k)
+                } else {
+                    color(x, y, i)
+                }
+            })
+        }
+    };
+    exit_if_stuck(options);
+
+    if measure {
+        log_dimension(&img, background);
+    }
+    if save.ascii {
+        print_ascii(&img, background);
+    } else {
+        handle_image(img.into(), dots, save);
+    }
+    if let Some(path) = &partial {
+        remove_checkpoint(path);
+    }
+}
+
+/// Log the box-counting dimension of the pixels of `img` that aren't `background`
+fn log_dimension<P>(img: &ImageBuffer<P, Vec<P::Subpixel>>, background: P)
+where
+    P: Pixel + PartialEq,
+{
+    match box_dimension(img, |px| *px != background) {
+        Some(dimension) => info!(
+            "The box-counting dimension is about {dimension:.3} (log 3 / log 2 = {:.3} for the Sierpiński triangle)",
+            3f64.log2()
+        ),
+        None => warn!("The image is too small or empty to measure its dimension"),
+    }
+}
+
+/// The characters of --ascii, from a cell without any dots to the fullest one
+const ASCII_RAMP: &[u8] = b" .:-=+*#";
+
+/// Number of characters across --ascii text when stdout isn't a terminal
+const ASCII_COLUMNS: u16 = 80;
+
+/// Print `img` as text as wide as the terminal, with a character from [`ASCII_RAMP`] for how many
+/// of the pixels in its cell aren't the `background`
+fn print_ascii<P>(img: &ImageBuffer<P, Vec<P::Subpixel>>, background: P)
+where
+    P: Pixel + PartialEq,
+{
+    let (width, height) = img.dimensions();
+    let (_, columns) = Term::stdout().size_checked().unwrap_or((0, ASCII_COLUMNS));
+    let columns = u64::from(columns).clamp(1, width.into());
+    // Characters are about twice as high as they're wide
+    let rows = (u64::from(height) * columns / u64::from(width) / 2).clamp(1, height.into());
+
+    let mut counts = vec![0u64; (columns * rows) as usize];
+    for (x, y, px) in img.enumerate_pixels() {
+        if *px != background {
+            let row = u64::from(y) * rows / u64::from(height);
+            let column = u64::from(x) * columns / u64::from(width);
+            counts[(row * columns + column) as usize] += 1;
+        }
+    }
+
+    let fullest = counts.iter().copied().max().unwrap_or_default().max(1);
+    let steps = ASCII_RAMP.len() as u64 - 2;
+    let mut text = String::new();
+    for row in counts.chunks(columns as usize) {
+        let line: String = row
+            .iter()
+            .map(|&count| {
+                // Any dot at all shows, so only empty cells are blank
+                let level = match count {
+                    0 => 0,
+                    _ => 1 + count * steps / fullest,
+                };
+                ASCII_RAMP[level as usize] as char
+            })
+            .collect();
+        text.push_str(line.trim_end());
+        text.push('\n');
+    }
+
+    io::stdout()
+        .lock()
+        .write_all(text.as_bytes())
+        .unwrap_or_else(|err| {
+            error!("Couldn't print the image: {err}");
+            process::exit(1);
+        });
+}
+
+/// Read the image at `path` to color the dots by, stretched to `width`x`height` if it's another size
+fn read_color_source(path: &str, width: u32, height: u32) -> RgbaImage {
+    let image = image::open(path).unwrap_or_else(|err| {
+        error!("Couldn't read the color source {path}: {err}");
+        process::exit(1);
+    });
+    let mut image = image.to_rgba8();
+    if image.dimensions() != (width, height) {
+        debug!(
+            "Stretching the {}x{} color source to {width}x{height}",
+            image.width(),
+            image.height()
+        );
+        image = imageops::resize(&image, width, height, FilterType::Triangle);
+    }
+
+    image
+}
+
+/// An affine map of an iterated function system, as written in its file
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MapEntry {
+    transform: [f64; 6],
+    probability: f64,
+}
+
+/// The maps of a TOML file, written as `[[map]]` tables
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlMaps {
+    #[serde(default)]
+    map: Vec<MapEntry>,
+}
+
+/// How far the probabilities of the maps may add up to more or less than 1, as they're often rounded
+const PROBABILITY_TOLERANCE: f64 = 0.001;
+
+/// Read the maps of an iterated function system from `path`, as a JSON list if it ends in .json and
+/// as TOML otherwise, checking there are any and their probabilities add up to 1
+fn read_maps(path: &str) -> Result<Vec<Map>, String> {
+    let text = fs::read_to_string(path).map_err(|err| format!("Couldn't read {path}: {err}"))?;
+    let json = Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+    let entries = if json {
+        serde_json::from_str(&text).map_err(|err| format!("Couldn't parse {path}: {err}"))?
+    } else {
+        toml::from_str::<TomlMaps>(&text)
+            .map(|maps| maps.map)
+            .map_err(|err| format!("Couldn't parse {path}: {err}"))?
+    };
+
+    if entries.is_empty() {
+        return Err(format!("{path} has no maps"));
+    }
+    for (n, entry) in entries.iter().enumerate() {
+        if !entry.transform.iter().all(|value| value.is_finite()) {
+            return Err(format!("The transform of map {} isn't all numbers", n + 1));
+        }
+        if !entry.probability.is_finite() || entry.probability < 0.0 {
+            return Err(format!(
+                "The probability of map {} is {}, but it can't be negative",
+                n + 1,
+                entry.probability
+            ));
+        }
+    }
+    let total: f64 = entries.iter().map(|entry| entry.probability).sum();
+    if (total - 1.0).abs() > PROBABILITY_TOLERANCE {
+        return Err(format!(
+            "The probabilities of the maps add up to {total} instead of 1"
+        ));
+    }
+
+    Ok(entries
+        .into_iter()
+        .map(|entry| (entry.transform, entry.probability))
+        .collect())
+}
+
+/// Read the mask at `path`, stretched to `width`x`height` if it's another size
+fn read_mask(path: &str, width: u32, height: u32) -> Mask {
+    let image = image::open(path).unwrap_or_else(|err| {
+        error!("Couldn't read the mask {path}: {err}");
+        process::exit(1);
+    });
+    let mut image = image.to_luma8();
+    if image.dimensions() != (width, height) {
+        debug!(
+            "Stretching the {}x{} mask to {width}x{height}",
+            image.width(),
+            image.height()
+        );
+        image = imageops::resize(&image, width, height, FilterType::Nearest);
+    }
+
+    let mask = Mask::from_image(&image);
+    if mask.count() == 0 {
+        error!("The mask {path} has no light pixels for the dots to land on");
+        process::exit(1);
+    }
+
+    mask
+}
+
+/// Log how many pixels the dots hit, how often the busiest of them was hit and how much of the
+/// image they cover, as counted in `hits` while they were placed
+fn log_stats(hits: &HitCounts) {
+    let hit = hits.hit_pixels();
+    info!(
+        "The dots hit {hit} pixels, the busiest of them {} times, covering {:.2}% of the image",
+        hits.busiest(),
+        hit as f64 * 100.0 / hits.pixels() as f64
+    );
+}
+
+/// Write the coordinates of the dots of a `width`x`height` image to a CSV file at `path`
+fn write_points(path: &str, width: u32, height: u32, dots: u64, options: &Options, force: bool) {
+    check_overwrite(Path::new(path), force);
+    let file = File::create(path).unwrap_or_else(|err| {
+        error!("Couldn't save the points to {path}: {err}");
+        process::exit(1);
+    });
+
+    info!("Exporting the points");
+    let save_time = Instant::now();
+    let mut writer = BufWriter::new(file);
+    let mut result = writeln!(writer, "dot,x,y");
+    make_points(width, height, dots, options, |i, x, y| {
+        // Stop writing at the first error, which is reported once all the dots are placed
+        if result.is_ok() {
+            result = writeln!(writer, "{i},{x},{y}");
+        }
+    });
+    result.and_then(|_| writer.flush()).unwrap_or_else(|err| {
+        error!("Couldn't save the points to {path}: {err}");
+        process::exit(1);
+    });
+    info!(
+        "Saved points to {path} in {}ms",
+        save_time.elapsed().as_millis()
+    );
+}
+
+/// Where the checkpoints of the image saved at `save_path` go, or `None` when it's written to stdout
+fn partial_path(save_path: &str) -> Option<String> {
+    if save_path == "stdout" {
+        warn!("Can't save checkpoints when writing the image to stdout, ignoring --checkpoint");
+        return None;
+    }
+
+    Some(format!("{save_path}.partial.png"))
+}
+
+/// Save the image after dot `i` at `path`, only warning when that fails so the image is still made
+fn save_checkpoint(img: DynamicImage, i: u64, path: &str, save: &Save) {
+    create_output_dir(save);
+    match finish(img, save).save_with_format(path, ImageFormat::Png) {
+        Ok(()) => debug!("Saved a checkpoint after {i} dots to {path}"),
+        Err(err) => warn!("Couldn't save a checkpoint to {path}: {err}"),
+    }
+}
+
+/// Remove the checkpoint at `path` now that the image is saved
+fn remove_checkpoint(path: &str) {
+    match fs::remove_file(path) {
+        Ok(()) => debug!("Removed the checkpoint {path}"),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => warn!("Couldn't remove the checkpoint {path}: {err}"),
+    }
+}
+
+/// Parse a color given on the command line, using white when it's missing or invalid (Unless `strict`)
+fn parse_color(hex: Option<&str>, strict: bool) -> Rgba<u8> {
+    let hex = match hex {
+        Some(hex) => hex,
+        None => {
+            info!("No hex color provided, using white.");
+            return Rgba([255, 255, 255, 255]);
+        }
+    };
+
+    get_color(hex).unwrap_or_else(|err| {
+        if strict {
+            error!("Invalid color {hex}: {err}");
+            process::exit(1);
+        }
+
+        warn!("{err}, using white.");
+        Rgba([255, 255, 255, 255])
+    })
+}
+
+/// Drop the alpha of a color for images without transparency
+fn to_rgb(color: Rgba<u8>) -> Rgb<u8> {
+    let Rgba([r, g, b, a]) = color;
+    if a != 255 {
+        warn!("The image has no transparency, ignoring the alpha of the color.");
+    }
+
+    Rgb([r, g, b])
+}
+
+/// Make sure an image with 16 bits per channel can be saved as the format it's saved as
+fn check_depth(save: &Save) {
+    let format = match save.output.as_deref() {
+        Some(path) if path != "-" => ImageFormat::from_path(path).ok(),
+        _ => Some(save.format),
+    };
+    if !matches!(format, Some(ImageFormat::Png | ImageFormat::Tiff)) {
+        error!("Images with 16 bits per channel can only be saved as PNG or TIFF");
+        process::exit(1);
+    }
+}
+
+/// Make sure a `width`x`height` image with `channels` bytes per pixel can be made
+fn check_dimensions(width: u32, height: u32, channels: u128, max_memory: Option<u64>) {
+    validate_dimensions(width, height, channels, max_memory).unwrap_or_else(|err| {
+        error!("{err}");
+        process::exit(1);
+    });
+}
+
+/// Least share of the pixels of an image the dots should cover for the fractal to be made out
+const MIN_COVERAGE: f64 = 0.02;
+
+/// Most dots that are placed without `--yes`
+const MAX_DOTS: u64 = 1_000_000_000;
+
+/// Check `dots` is at most [`MAX_DOTS`], unless `yes` goes ahead anyway
+fn validate_dots(dots: u64, yes: bool) -> Result<(), String> {
+    if dots > MAX_DOTS && !yes {
+        return Err(format!(
+            "{dots} dots would take hours to place (More than {MAX_DOTS}), use --yes to place them anyway"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Warn when `dots` dots of `dot_size` pixels are too few to see much on a `width`x`height` image,
+/// or so many they take hours, stopping when there are more than [`MAX_DOTS`] without `yes`
+fn check_dots(width: u32, height: u32, dots: u64, dot_size: u32, yes: bool) {
+    validate_dots(dots, yes).unwrap_or_else(|err| {
+        error!("{err}");
+        process::exit(1);
+    });
+    if dots > MAX_DOTS {
+        warn!("Placing {dots} dots, which can take hours");
+    }
+
+    let pixels = width as f64 * height as f64;
+    let area = (dot_size as f64).powi(2);
+    if dots as f64 * area < pixels * MIN_COVERAGE {
+        let suggested = (pixels * MIN_COVERAGE / area).ceil();
+        warn!("{dots} dots are very few for a {width}x{height} image, so the fractal will be hard to see. Try at least {suggested} dots");
+    }
+}
+
+/// Smallest width and height of an image to add a triangle to, so the triangle has a margin
+const MIN_IMAGE_SIZE: u32 = 10;
+
+fn check_image_size(image: &str, width: u32, height: u32) {
+    if width < MIN_IMAGE_SIZE || height < MIN_IMAGE_SIZE {
+        error!("{image} is {width}x{height}, but it must be at least {MIN_IMAGE_SIZE}x{MIN_IMAGE_SIZE} pixels");
+        process::exit(1);
+    }
+}
+
+/// Check a `width`x`height` image with `channels` bytes per pixel can be made, within `max_memory`
+fn validate_dimensions(
+    width: u32,
+    height: u32,
+    channels: u128,
+    max_memory: Option<u64>,
+) -> Result<(), String> {
+    if width == 0 || height == 0 {
+        return Err(format!(
+            "The image must be at least 1 pixel wide and high, but it would be {width}x{height}"
+        ));
+    }
+
+    let bytes = width as u128 * height as u128 * channels;
+    let size = format_size(bytes);
+    if bytes > isize::MAX as u128 {
+        return Err(format!(
+            "A {width}x{height} image would need {size} of memory, which is too much to allocate"
+        ));
+    }
+
+    if let Some(max) = max_memory.filter(|&max| bytes > max as u128) {
+        // Scale both sides down by the same factor, so the suggestion keeps the aspect ratio
+        let factor = (max as f64 / bytes as f64).sqrt();
+        return Err(format!(
+            "A {width}x{height} image would need {size} of memory, more than the {} of --max-memory (Try {}x{} or smaller)",
+            format_size(max as u128),
+            (width as f64 * factor) as u32,
+            (height as f64 * factor) as u32
+        ));
+    }
+
+    // Reserving the memory up front turns a failed allocation into an error instead of an abort
+    if Vec::<u8>::new().try_reserve_exact(bytes as usize).is_err() {
+        return Err(format!(
+            "Not enough memory for a {width}x{height} image ({size})"
+        ));
+    }
+
+    Ok(())
+}
+
+fn format_size(bytes: u128) -> String {
+    let bytes = bytes as f64;
+    if bytes >= (1u64 << 30) as f64 {
+        format!("{:.1} GiB", bytes / (1u64 << 30) as f64)
+    } else if bytes >= (1u64 << 20) as f64 {
+        format!("{:.1} MiB", bytes / (1u64 << 20) as f64)
+    } else if bytes >= 1024.0 {
+        format!("{:.1} KiB", bytes / 1024.0)
+    } else {
+        format!("{bytes} B")
+    }
+}
+
+fn format_positions(positions: &[[u32; 2]]) -> String {
+    positions
+        .iter()
+        .map(|[x, y]| format!("{x},{y}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// A color as a hex code, with the alpha only when it isn't opaque
+fn to_hex(Rgba([r, g, b, a]): Rgba<u8>) -> String {
+    if a == 255 {
+        format!("#{r:02x}{g:02x}{b:02x}")
+    } else {
+        format!("#{r:02x}{g:02x}{b:02x}{a:02x}")
+    }
+}
+
+/// Print the settings resolved for `--dry-run`, one per line
+fn print_plan(plan: &[(&str, String)]) {
+    let width = plan.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    for (name, value) in plan {
+        println!("{name:width$}  {value}");
+    }
+}
+
+fn parse_size(s: &str) -> Result<u64, String> {
+    let upper = s.trim().to_ascii_uppercase();
+    let number = upper
+        .trim_end_matches('B')
+        .trim_end_matches('I')
+        .trim_end_matches(['K', 'M', 'G', 'T']);
+    let unit = match upper[number.len()..].chars().next() {
+        Some('K') => 1u64 << 10,
+        Some('M') => 1 << 20,
+        Some('G') => 1 << 30,
+        Some('T') => 1 << 40,
+        _ => 1,
+    };
+    let size: f64 = number
+        .trim()
+        .parse()
+        .map_err(|err| format!("{s} isn't a size like 512M or 4G: {err}"))?;
+
+    Ok((size * unit as f64) as u64)
+}
+
+fn parse_vertex(s: &str) -> Result<[u32; 2], String> {
+    let (x, y) = s
+        .split_once(',')
+        .ok_or_else(|| format!("{s} isn't of the form X,Y"))?;
+    let x = x.trim().parse().map_err(|err| format!("{x}: {err}"))?;
+    let y = y.trim().parse().map_err(|err| format!("{y}: {err}"))?;
+
+    Ok([x, y])
+}
+
+fn check_vertices(positions: &[[u32; 2]], vertices: usize, width: u32, height: u32) {
+    if positions.len() != vertices {
+        error!(
+            "Expected {vertices} vertices, but {} were given",
+            positions.len()
+        );
+        process::exit(1);
+    }
+
+    for &[x, y] in positions {
+        if x >= width || y >= height {
+            error!("The vertex {x},{y} is outside of the {width}x{height} image");
+            process::exit(1);
+        }
+    }
+}
+
+fn check_weights(weights: &[f64], vertices: usize, restrict: Restriction) {
+    if weights.len() != vertices {
+        error!(
+            "Expected {vertices} weights, one for every vertex, but {} were given",
+            weights.len()
+        );
+        process::exit(1);
+    }
+
+    // Every vertex that can be chosen needs one it's allowed to be followed by
+    let chosen: Vec<_> = (0..vertices).filter(|&n| weights[n] > 0.0).collect();
+    if chosen.iter().any(|&prev| {
+        !chosen
+            .iter()
+            .any(|&next| restrict.allows(prev, next, vertices))
+    }) {
+        error!(
+            "The weights leave no vertex to jump towards with --restrict {}",
+            restrict.to_possible_value().unwrap().get_name()
+        );
+        process::exit(1);
+    }
+}
+
+fn parse_tile(s: &str) -> Result<[u32; 2], String> {
+    let (cols, rows) = s
+        .split_once('x')
+        .ok_or_else(|| format!("{s} isn't of the form COLSxROWS"))?;
+    let cols: u32 = cols
+        .trim()
+        .parse()
+        .map_err(|err| format!("{cols}: {err}"))?;
+    let rows: u32 = rows
+        .trim()
+        .parse()
+        .map_err(|err| format!("{rows}: {err}"))?;
+    if cols == 0 || rows == 0 {
+        return Err(format!("{s} has no tiles"));
+    }
+
+    Ok([cols, rows])
+}
+
+/// Parse a width to height ratio like `16:9` (Both parts positive, and not necessarily whole)
+fn parse_aspect(s: &str) -> Result<[f64; 2], String> {
+    let (width, height) = s
+        .split_once(':')
+        .ok_or_else(|| format!("{s} isn't of the form W:H"))?;
+    let parse = |part: &str| match part.trim().parse::<f64>() {
+        Ok(value) if value.is_finite() && value > 0.0 => Ok(value),
+        Ok(_) => Err(format!("{part} isn't a positive number")),
+        Err(err) => Err(format!("{part}: {err}")),
+    };
+
+    Ok([parse(width)?, parse(height)?])
+}
+
+/// The width and height of an image with the `aspect` ratio, from the one of `width` and `height`
+/// that's given (Or checking they match it when both are)
+fn aspect_size(
+    width: Option<u32>,
+    height: Option<u32>,
+    aspect: [f64; 2],
+) -> Result<(u32, u32), String> {
+    let [aspect_width, aspect_height] = aspect;
+    let ratio = aspect_width / aspect_height;
+    let side = |size: f64| match size.round() {
+        size if size < 1.0 => Err("The image would be less than a pixel wide or high".to_string()),
+        size if size > u32::MAX as f64 => Err("The image would be too large".to_string()),
+        size => Ok(size as u32),
+    };
+
+    match (width, height) {
+        (Some(width), Some(height)) => {
+            // Either side rounded from the other is close enough
+            let matches = side(width as f64 / ratio) == Ok(height)
+                || side(height as f64 * ratio) == Ok(width);
+            if !matches {
+                return Err(format!(
+                    "A {width}x{height} image isn't {aspect_width}:{aspect_height}, so leave out --width or --height"
+                ));
+            }
+            Ok((width, height))
+        }
+        (Some(width), None) => Ok((width, side(width as f64 / ratio)?)),
+        (None, Some(height)) => Ok((side(height as f64 * ratio)?, height)),
+        (None, None) => Err("--aspect needs the --width or --height of the image".to_string()),
+    }
+}
+
+fn check_tiles([cols, rows]: [u32; 2], width: u32, height: u32) {
+    if cols > width || rows > height {
+        error!("A {width}x{height} image can't be split into {cols}x{rows} tiles");
+        process::exit(1);
+    }
+}
+
+fn parse_seconds(s: &str) -> Result<f64, String> {
+    let seconds: f64 = s.parse().map_err(|err| format!("{err}"))?;
+    if seconds > 0.0 && seconds.is_finite() {
+        Ok(seconds)
+    } else {
+        Err(format!("{seconds} is not a positive number of seconds"))
+    }
+}
+
+fn parse_ratio(s: &str) -> Result<f64, String> {
+    let ratio: f64 = s.parse().map_err(|err| format!("{err}"))?;
+    if ratio > 0.0 && ratio < 1.0 {
+        Ok(ratio)
+    } else {
+        Err(format!("{ratio} is not strictly between 0 and 1"))
+    }
+}
+
+fn parse_weights(s: &str) -> Result<Weights, String> {
+    let weights = s
+        .split(',')
+        .map(|weight| {
+            let weight: f64 = weight.trim().parse().map_err(|err| format!("{err}"))?;
+            if weight >= 0.0 && weight.is_finite() {
+                Ok(weight)
+            } else {
+                Err(format!("{weight} is not a non-negative number"))
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if weights.iter().all(|&weight| weight == 0.0) {
+        return Err("At least one weight must be more than 0".to_string());
+    }
+
+    Ok(Weights(weights))
+}
+
+fn parse_margin(s: &str) -> Result<f64, String> {
+    let margin: f64 = s.parse().map_err(|err| format!("{err}"))?;
+    if (0.0..=0.4).contains(&margin) {
+        Ok(margin)
+    } else {
+        Err(format!("{margin} is not between 0 and 0.4"))
+    }
+}
+
+fn parse_depth(s: &str) -> Result<u8, String> {
+    match s {
+        "8" => Ok(8),
+        "16" => Ok(16),
+        _ => Err(format!("{s} bits isn't supported, only 8 or 16")),
+    }
+}
+
+fn parse_hex_color(s: &str) -> Result<Rgba<u8>, String> {
+    get_color(s).map_err(|err| err.to_string())
+}
+
+fn parse_format(s: &str) -> Result<ImageFormat, String> {
+    ImageFormat::from_extension(s)
+        .filter(can_save)
+        .ok_or_else(|| format!("{s} isn't a supported image format"))
+}
+
+/// Whether images can be saved as `format`, which for WebP is done with libwebp as the image
+/// crate can't write it by itself
+fn can_save(format: &ImageFormat) -> bool {
+    format.can_write() || *format == ImageFormat::WebP
+}
+
+/// Apply the last touches asked for in `save` to the finished image, right before it's saved
+fn finish(mut img: DynamicImage, save: &Save) -> DynamicImage {
+    if save.supersample > 1 {
+        let (width, height) = (
+            img.width() / save.supersample,
+            img.height() / save.supersample,
+        );
+        debug!("Shrinking the supersampled image down to {width}x{height}");
+        img = img.thumbnail_exact(width, height);
+    }
+
+    if save.invert {
+        debug!("Inverting the colors");
+        img.invert();
+    }
+
+    if save.scale > 1 {
+        let (width, height) = scaled(img.width(), img.height(), save);
+        debug!("Scaling the image up to {width}x{height}");
+        img = img.resize_exact(width, height, FilterType::Nearest);
+    }
+
+    if save.border > 0 {
+        debug!("Drawing a {} pixel border", save.border);
+        draw_border(&mut img, save.border, save.border_color);
+    }
+
+    img
+}
+
+/// Fill the outer `border` pixels of `img` with `color`
+fn draw_border(img: &mut DynamicImage, border: u32, color: Rgba<u8>) {
+    let (width, height) = img.dimensions();
+    if border.saturating_mul(2) >= width.min(height) {
+        warn!("A {border} pixel border covers the whole {width}x{height} image");
+    }
+
+    for y in 0..height {
+        for x in 0..width {
+            if x < border
+                || y < border
+                || x >= width.saturating_sub(border)
+                || y >= height.saturating_sub(border)
+            {
+                img.put_pixel(x, y, color);
+            }
+        }
+    }
+}
+
+/// The size of a `width`x`height` image after scaling it by `--scale`
+fn scaled(width: u32, height: u32, save: &Save) -> (u32, u32) {
+    match (
+        width.checked_mul(save.scale),
+        height.checked_mul(save.scale),
+    ) {
+        (Some(width), Some(height)) => (width, height),
+        _ => {
+            error!(
+                "A {width}x{height} image is too large to scale by {}",
+                save.scale
+            );
+            process::exit(1);
+        }
+    }
+}
+
+/// Time each frame of `--animate` is shown (In milliseconds)
+const ANIMATION_DELAY: u32 = 100;
+
+/// Time each frame of `--spin` is shown, making 25 frames a second (In milliseconds)
+const SPIN_DELAY: u32 = 40;
+
+/// Memory the frames of a spin can take before warning about it, and the size of the file
+const SPIN_MEMORY: u128 = 1 << 30;
+
+/// The number of frames of a spin lasting `seconds`
+fn spin_frames(seconds: f64) -> u32 {
+    (seconds * 1000.0 / SPIN_DELAY as f64).round().max(1.0) as u32
+}
+
+/// Move `positions` towards the center of a `width`x`height` image until they stay inside it at
+/// every angle they're turned to
+fn spin_positions(positions: &[[u32; 2]], width: u32, height: u32) -> Vec<[u32; 2]> {
+    let (cx, cy) = (width as f64 / 2.0, height as f64 / 2.0);
+    let farthest = positions
+        .iter()
+        .map(|&[x, y]| (x as f64 - cx).hypot(y as f64 - cy))
+        .fold(0.0, f64::max);
+    let radius = cx.min(cy) - 1.0;
+    if farthest <= radius {
+        return positions.to_vec();
+    }
+
+    debug!("Moving the vertices in so they stay on the image while it spins");
+    let scale = radius.max(0.0) / farthest;
+    positions
+        .iter()
+        .map(|&[x, y]| {
+            [
+                (cx + (x as f64 - cx) * scale).round() as u32,
+                (cy + (y as f64 - cy) * scale).round() as u32,
+            ]
+        })
+        .collect()
+}
+
+/// The file formats animations can be saved as
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum AnimationFormat {
+    Gif,
+    Png,
+    Mp4,
+}
+
+impl AnimationFormat {
+    /// The format of an animation saved to `path`, by its extension
+    fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "gif" => Some(AnimationFormat::Gif),
+            "png" | "apng" => Some(AnimationFormat::Png),
+            "mp4" => Some(AnimationFormat::Mp4),
+            _ => None,
+        }
+    }
+}
+
+fn handle_animation(frames: Vec<DynamicImage>, dots: u64, delay: u32, save: &Save) {
+    let frames: Vec<DynamicImage> = frames
+        .into_iter()
+        .map(|frame| finish(frame, save))
+        .collect();
+    if let Some(dir) = &save.frames_dir {
+        save_frames(&frames, dir, save);
+        // The frames are all that's saved unless an --output is given too
+        if save.output.is_none() {
+            return;
+        }
+    }
+    let (width, height) = frames[0].dimensions();
+    let to_stdout = is_stdout(save);
+    let save_path = PathBuf::from(output_path(width, height, dots, true, save));
+    let format = match to_stdout {
+        true => AnimationFormat::Gif,
+        false => AnimationFormat::from_path(&save_path).unwrap_or(AnimationFormat::Gif),
+    };
+
+    if format == AnimationFormat::Mp4 {
+        create_output_dir(save);
+        check_overwrite(&save_path, save.force);
+        info!("Saving animation as MP4");
+        let save_time = Instant::now();
+        write_mp4(&frames, delay, &save_path).unwrap_or_else(|err| {
+            error!("Couldn't save to {}: {err}", save_path.display());
+            process::exit(1);
+        });
+        info!("Saved animation in {}ms", save_time.elapsed().as_millis());
+        write_metadata(&save_path.display().to_string(), save);
+        finish_animation(&save_path, to_stdout, save);
+        return;
+    }
+
+    let writer: Box<dyn Write> = if to_stdout {
+        info!("Writing animation to stdout");
+        Box::new(io::stdout().lock())
     } else {
-        save_path = format!(
-            "{}x{} - {}.png",
-            img.dimensions().0,
-            img.dimensions().1,
-            dots
-        );
+        create_output_dir(save);
+        check_overwrite(&save_path, save.force);
+        info!("Saving animation");
+        Box::new(File::create(&save_path).unwrap_or_else(|err| {
+            error!("Couldn't save to {}: {err}", save_path.display());
+            process::exit(1);
+        }))
+    };
+
+    let save_time = Instant::now();
+    let result = match format {
+        AnimationFormat::Png => write_apng(frames, delay, writer, save),
+        _ => {
+            let mut encoder = GifEncoder::new(writer);
+            encoder.set_repeat(Repeat::Infinite).and_then(|_| {
+                encoder.encode_frames(frames.into_iter().map(|frame| {
+                    Frame::from_parts(
+                        frame.into_rgba8(),
+                        0,
+                        0,
+                        Delay::from_numer_denom_ms(delay, 1),
+                    )
+                }))
+            })
+        }
+    };
+    result.unwrap_or_else(|err| {
+        error!("Couldn't save to {}: {err}", save_path.display());
+        process::exit(1);
+    });
+    info!("Saved animation in {}ms", save_time.elapsed().as_millis());
+    if !to_stdout {
+        write_metadata(&save_path.display().to_string(), save);
+    }
+
+    finish_animation(&save_path, to_stdout, save);
+}
+
+/// Fewest digits the frames saved with --frames-dir are numbered with
+const FRAME_DIGITS: usize = 4;
+
+/// Save every frame of an animation as a numbered PNG in `dir`, making it if it's missing
+fn save_frames(frames: &[DynamicImage], dir: &Path, save: &Save) {
+    fs::create_dir_all(dir).unwrap_or_else(|err| {
+        error!("Couldn't make the directory {}: {err}", dir.display());
+        process::exit(1);
+    });
+
+    info!("Saving {} frames to {}", frames.len(), dir.display());
+    let save_time = Instant::now();
+    // The numbers are padded with zeros so the frames sort in order
+    let digits = (frames.len() - 1).to_string().len().max(FRAME_DIGITS);
+    for (n, frame) in frames.iter().enumerate() {
+        let path = dir.join(format!("frame-{n:0digits$}.png"));
+        check_overwrite(&path, save.force);
+        frame
+            .save_with_format(&path, ImageFormat::Png)
+            .unwrap_or_else(|err| {
+                error!("Couldn't save to {}: {err}", path.display());
+                process::exit(1);
+            });
+    }
+    info!("Saved frames in {}ms", save_time.elapsed().as_millis());
+}
+
+/// Warn about what can't be done with a saved animation, and preview it
+fn finish_animation(save_path: &Path, to_stdout: bool, save: &Save) {
+    if save.wallpaper {
+        warn!("An animation can't be set as wallpaper, ignoring --wallpaper");
     }
 
-    img.save(&save_path).unwrap();
+    if save.clipboard {
+        warn!("An animation can't be copied to the clipboard, ignoring --clipboard");
+    }
 
-    if wallpaper {
-        info!("Setting image as wallpaper");
-        wallpaper::set_from_path(fs::canonicalize(save_path).unwrap().to_str().unwrap()).unwrap();
+    if save.preview && !to_stdout {
+        preview(&save_path.display().to_string());
     }
 }
 
-fn make_image<F>(
-    image: ImageBuffer<Rgb<u8>, Vec<u8>>,
-    dots: u64,
-    color: F,
-) -> ImageBuffer<Rgb<u8>, Vec<u8>>
-where
-    F: Fn(u32, u32) -> Rgb<u8>,
-{
-    let width = image.dimensions().0;
-    let height = image.dimensions().1;
-    info!("Creating a Sierpiński triangle with {dots} points on a {width}x{height} image");
-    let positions = [
-        [width / 10, height - (height / 10)],
-        [width - (width / 10), height - (height / 10)],
-        [width / 2, height / 10],
-    ];
-
-    info!("Creating image");
-    let mut img = image;
-    let mut last = [width / 2, height / 2 - 1];
-
-    info!("Placing corners");
-    for [x, y] in positions {
-        img.put_pixel(x, y, color(x, y));
-    }
-
-    info!("Placing dots");
-    let mut rng = thread_rng();
-    let bar = ProgressBar::new(dots);
-    for i in 1..=dots {
-        let n = rng.gen_range(0..=2);
-        img.put_pixel(last[0], last[1], color(last[0], last[1]));
-        last = [
-            ((last[0] + positions[n][0]) / 2),
-            ((last[1] + positions[n][1]) / 2),
-        ];
-        if i % 1000 == 0 {
-            bar.inc(1000);
-        }
-    }
-    bar.finish();
+/// Write `frames` as an animated PNG showing each of them for `delay` milliseconds, with the
+/// settings of `save` in text chunks
+fn write_apng<W: Write>(
+    frames: Vec<DynamicImage>,
+    delay: u32,
+    writer: W,
+    save: &Save,
+) -> ImageResult<()> {
+    let (width, height) = frames[0].dimensions();
+    let encoding = |err: png::EncodingError| ImageError::IoError(io::Error::other(err));
+    let mut encoder = png::Encoder::new(writer, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .set_animated(frames.len() as u32, 0)
+        .and_then(|_| encoder.set_frame_delay(delay as u16, 1000))
+        .map_err(encoding)?;
+    if let Some(settings) = &save.settings {
+        add_metadata_chunks(&mut encoder, settings).map_err(encoding)?;
+    }
 
-    img
+    let mut writer = encoder.write_header().map_err(encoding)?;
+    for frame in frames {
+        writer
+            .write_image_data(&frame.into_rgba8().into_raw())
+            .map_err(encoding)?;
+    }
+    writer.finish().map_err(encoding)
+}
+
+/// Write `frames` as an MP4 video at `path` showing each of them for `delay` milliseconds, by
+/// piping them to ffmpeg
+fn write_mp4(frames: &[DynamicImage], delay: u32, path: &Path) -> io::Result<()> {
+    let (width, height) = frames[0].dimensions();
+    let mut ffmpeg = process::Command::new("ffmpeg")
+        .args([
+            "-y",
+            "-loglevel",
+            "error",
+            "-f",
+            "rawvideo",
+            "-pix_fmt",
+            "rgba",
+        ])
+        .args(["-s", &format!("{width}x{height}")])
+        .args(["-framerate", &format!("1000/{delay}"), "-i", "-"])
+        // Most players only take even sizes in the usual pixel format
+        .args([
+            "-vf",
+            "pad=ceil(iw/2)*2:ceil(ih/2)*2",
+            "-pix_fmt",
+            "yuv420p",
+        ])
+        .arg(path)
+        .stdin(process::Stdio::piped())
+        .spawn()
+        .map_err(|err| io::Error::new(err.kind(), format!("Couldn't run ffmpeg: {err}")))?;
+
+    let mut stdin = ffmpeg.stdin.take().expect("the input of ffmpeg is piped");
+    let written = frames
+        .iter()
+        .try_for_each(|frame| stdin.write_all(frame.to_rgba8().as_raw()));
+    // ffmpeg only finishes the video once its input is closed
+    drop(stdin);
+    let status = ffmpeg.wait()?;
+    written?;
+    if !status.success() {
+        return Err(io::Error::other(format!("ffmpeg failed with {status}")));
+    }
+
+    Ok(())
 }
 
-fn get_color(hex: Option<String>) -> Rgb<u8> {
-    if let Some(hex_code) = hex {
-        if hex_code.is_empty() {
-            warn!("No hex color provided, using white.");
-            return Rgb([255, 255, 255]);
+fn handle_image(img: DynamicImage, dots: u64, save: &Save) {
+    let img = finish(img, save);
+    if save.clipboard {
+        copy_to_clipboard(&img);
+    }
+
+    if is_stdout(save) {
+        info!("Writing image to stdout");
+        let mut bytes = Cursor::new(Vec::new());
+        write_image(&img, &mut bytes, save.format, save)
+            .and_then(|_| Ok(io::stdout().lock().write_all(bytes.get_ref())?))
+            .unwrap_or_else(|err| {
+                error!("Couldn't write the image to stdout: {err}");
+                process::exit(1);
+            });
+
+        return;
+    }
+
+    if let Some(path) = save.output.as_deref().filter(|path| is_svg(path)) {
+        if dots > SVG_DOTS {
+            warn!("SVG images with more than {SVG_DOTS} dots can get very large");
+        }
+        check_overwrite(Path::new(path), save.force);
+
+        info!("Saving image as SVG");
+        let save_time = Instant::now();
+        File::create(path)
+            .and_then(|file| write_svg(&img.to_rgba8(), BufWriter::new(file)))
+            .unwrap_or_else(|err| {
+                error!("Couldn't save to {path}: {err}");
+                process::exit(1);
+            });
+        info!("Saved image in {}ms", save_time.elapsed().as_millis());
+        write_metadata(path, save);
+
+        if save.wallpaper {
+            warn!("An SVG image can't be set as wallpaper, ignoring --wallpaper");
+        }
+
+        if save.preview {
+            preview(path);
         }
 
-        // Remove # from hex code
-        let mut hex_code = if let Some(stripped) = hex_code.strip_prefix('#') {
-            stripped.to_string()
+        return;
+    }
+
+    info!("Saving image");
+    create_output_dir(save);
+    let save_path = output_path(img.width(), img.height(), dots, false, save);
+    let format = if save.output.is_some() {
+        ImageFormat::from_path(&save_path)
+            .ok()
+            .filter(can_save)
+            .unwrap_or_else(|| {
+                error!("Couldn't save to {save_path}: The file extension isn't a supported image format");
+                process::exit(1);
+            })
+    } else {
+        save.format
+    };
+    check_overwrite(Path::new(&save_path), save.force);
+
+    let save_time = Instant::now();
+    File::create(&save_path)
+        .map_err(ImageError::IoError)
+        .and_then(|file| write_image(&img, BufWriter::new(file), format, save))
+        .unwrap_or_else(|err| {
+            error!("Couldn't save to {save_path}: {err}");
+            process::exit(1);
+        });
+    info!("Saved image in {}ms", save_time.elapsed().as_millis());
+    write_metadata(&save_path, save);
+
+    if save.wallpaper {
+        if format == ImageFormat::WebP && cfg!(windows) {
+            warn!("Windows can't show WebP images as wallpaper, ignoring --wallpaper");
         } else {
-            hex_code
-        };
+            set_wallpaper(&save_path, save.wallpaper_mode);
+        }
+    }
+
+    if save.preview {
+        preview(&save_path);
+    }
+}
+
+/// Write `img` as `format`, with the settings of `save` in text chunks when it's a PNG and its
+/// `--quality` when it's a JPEG or WebP
+fn write_image<W: Write + Seek>(
+    img: &DynamicImage,
+    mut writer: W,
+    format: ImageFormat,
+    save: &Save,
+) -> ImageResult<()> {
+    match (format, save.quality) {
+        (ImageFormat::WebP, quality) => {
+            let bytes = encode_webp(img, quality)
+                .map_err(|err| ImageError::IoError(io::Error::other(err)))?;
+            return writer.write_all(&bytes).map_err(ImageError::IoError);
+        }
+        (ImageFormat::Jpeg, Some(quality)) => {
+            let rgb = img.to_rgb8();
+            return JpegEncoder::new_with_quality(&mut writer, quality).encode(
+                rgb.as_raw(),
+                rgb.width(),
+                rgb.height(),
+                ColorType::Rgb8,
+            );
+        }
+        _ => {}
+    }
+
+    let settings = match &save.settings {
+        Some(settings) if format == ImageFormat::Png => settings,
+        _ => return img.write_to(&mut writer, format),
+    };
+
+    let (width, height) = img.dimensions();
+    let sixteen = img.color().bytes_per_pixel() > img.color().channel_count();
+    let (color, data) = match img.color().has_alpha() {
+        true if sixteen => (
+            png::ColorType::Rgba,
+            to_be_bytes(img.to_rgba16().into_raw()),
+        ),
+        false if sixteen => (png::ColorType::Rgb, to_be_bytes(img.to_rgb16().into_raw())),
+        true => (png::ColorType::Rgba, img.to_rgba8().into_raw()),
+        false => (png::ColorType::Rgb, img.to_rgb8().into_raw()),
+    };
+
+    let mut encoder = png::Encoder::new(writer, width, height);
+    encoder.set_color(color);
+    encoder.set_depth(if sixteen {
+        png::BitDepth::Sixteen
+    } else {
+        png::BitDepth::Eight
+    });
+    let encoding = |err: png::EncodingError| ImageError::IoError(io::Error::other(err));
+    add_metadata_chunks(&mut encoder, settings).map_err(encoding)?;
+
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&data))
+        .map_err(encoding)
+}
+
+/// Add the settings as text chunks of the PNG image being written by `encoder`
+fn add_metadata_chunks<W: Write>(
+    encoder: &mut png::Encoder<W>,
+    settings: &Metadata,
+) -> Result<(), png::EncodingError> {
+    for (key, value) in metadata_entries(settings) {
+        let keyword = format!("{METADATA_PREFIX}{key}");
+        // tEXt chunks only hold Latin-1, so anything else (Like paths) goes in a UTF-8 iTXt chunk
+        if value.is_ascii() {
+            encoder.add_text_chunk(keyword, value)?;
+        } else {
+            encoder.add_itxt_chunk(keyword, value)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The bytes of 16 bit channels in the big endian order of PNG images
+fn to_be_bytes(channels: Vec<u16>) -> Vec<u8> {
+    channels.iter().flat_map(|c| c.to_be_bytes()).collect()
+}
+
+/// Prefix of the keywords of the text chunks holding the settings in PNG images
+const METADATA_PREFIX: &str = "sierpinski:";
+
+/// The settings as pairs of names and values, leaving out the ones that aren't known
+fn metadata_entries(settings: &Metadata) -> Vec<(String, String)> {
+    let value = serde_json::to_value(settings).expect("the settings are always valid JSON");
+    let fields = match value {
+        serde_json::Value::Object(fields) => fields,
+        _ => unreachable!("the settings are a struct"),
+    };
+
+    fields
+        .into_iter()
+        .filter(|(_, value)| !value.is_null())
+        .map(|(key, value)| match value {
+            serde_json::Value::String(value) => (key, value),
+            value => (key, value.to_string()),
+        })
+        .collect()
+}
+
+/// Read the settings back from the text chunks of the PNG image at `path`
+fn read_metadata(path: &str) -> Result<Vec<(String, String)>, String> {
+    let file = File::open(path).map_err(|err| format!("Couldn't open {path}: {err}"))?;
+    let reader = png::Decoder::new(BufReader::new(file))
+        .read_info()
+        .map_err(|err| format!("Couldn't read {path} as a PNG image: {err}"))?;
+    let info = reader.info();
+
+    let latin1 = info
+        .uncompressed_latin1_text
+        .iter()
+        .map(|chunk| (chunk.keyword.clone(), chunk.text.clone()));
+    let utf8 = info
+        .utf8_text
+        .iter()
+        .filter_map(|chunk| Some((chunk.keyword.clone(), chunk.get_text().ok()?)));
+
+    Ok(latin1
+        .chain(utf8)
+        .filter_map(|(keyword, text)| {
+            Some((keyword.strip_prefix(METADATA_PREFIX)?.to_string(), text))
+        })
+        .collect())
+}
+
+/// Make the image `settings` read from a PNG image describe again, with a transparent background
+/// when `transparent` and a black one otherwise
+fn remake_image(
+    settings: &[(String, String)],
+    transparent: bool,
+    progress: bool,
+) -> Result<DynamicImage, String> {
+    let field = |key: &str| {
+        settings
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+    };
+    fn parse<T: std::str::FromStr>(key: &str, value: Option<&str>) -> Result<T, String> {
+        value
+            .ok_or_else(|| format!("It has no {key} saved"))?
+            .parse()
+            .map_err(|_| format!("Its {key} isn't valid"))
+    }
+
+    // Only images made with generate have a restriction, which the others can't be made again with
+    let restrict = Restriction::from_str(
+        field("restriction").ok_or("It wasn't made with generate")?,
+        true,
+    )
+    .map_err(|_| "Its restriction isn't valid".to_string())?;
+    let width: u32 = parse("width", field("width"))?;
+    let height: u32 = parse("height", field("height"))?;
+    let dots: u64 = parse("dots", field("dots"))?;
+    let seed: u64 = parse("seed", field("seed"))?;
+    let ratio: f64 = parse("ratio", field("ratio"))?;
+    let weights: Option<Vec<f64>> = field("weights")
+        .map(|weights| serde_json::from_str(weights).map_err(|_| "Its weights aren't valid"))
+        .transpose()?;
+    let generator = field("rng")
+        .map(|rng| Generator::from_str(rng, true).map_err(|_| "Its rng isn't valid"))
+        .transpose()?
+        .unwrap_or(Generator::Standard);
+    // Images saved before the vertices were have one for every weight, or 3
+    let vertices = match field("vertices") {
+        Some(vertices) => parse::<usize>("vertices", Some(vertices))?,
+        None => weights.as_ref().map_or(3, Vec::len),
+    };
+    let supersample = field("supersample")
+        .map(|supersample| parse::<u32>("supersample", Some(supersample)))
+        .transpose()?
+        .unwrap_or(1)
+        .max(1);
+    let color = field("color")
+        .filter(|color| color.starts_with('#'))
+        .and_then(|color| get_color(color).ok())
+        .ok_or("Only images of a single color can be made again")?;
+
+    let (made_width, made_height) = width
+        .checked_mul(supersample)
+        .zip(height.checked_mul(supersample))
+        .ok_or("Its supersampled size is too large")?;
+    let options = Options {
+        seed: Some(seed),
+        vertices,
+        restrict,
+        weights,
+        ratio,
+        generator,
+        progress,
+        ..Options::default()
+    };
+    let dots = dots.saturating_mul(u64::from(supersample).pow(2));
+
+    let img = if transparent {
+        DynamicImage::from(make_image(
+            RgbaImage::new(made_width, made_height),
+            dots,
+            &options,
+            |_, _, _| color,
+        ))
+    } else {
+        DynamicImage::from(make_image(
+            RgbImage::new(made_width, made_height),
+            dots,
+            &options,
+            |_, _, _| color.to_rgb(),
+        ))
+    };
+
+    Ok(match supersample {
+        1 => img,
+        _ => img.thumbnail_exact(width, height),
+    })
+}
+
+/// Save the settings of `save` next to the image at `path` (If --metadata is on)
+///
+/// This only warns when it fails, as the image itself is saved already.
+fn write_metadata(path: &str, save: &Save) {
+    let settings = match &save.settings {
+        Some(settings) if save.metadata => settings,
+        _ => return,
+    };
+
+    let json_path = format!("{path}.json");
+    File::create(&json_path)
+        .map_err(serde_json::Error::io)
+        .and_then(|file| serde_json::to_writer_pretty(BufWriter::new(file), settings))
+        .map_or_else(
+            |err| warn!("Couldn't save the settings to {json_path}: {err}"),
+            |_| info!("Saved the settings to {json_path}"),
+        );
+}
+
+/// How far the colors of a --bg-noise background are moved from the --background at most
+const NOISE_AMOUNT: f64 = 24.0;
+
+/// How far around the color wheel --hue-map goes from left to right (In degrees, short of a full
+/// turn so both sides aren't red)
+const HUE_RANGE: f64 = 300.0;
+
+/// Dots above which SVG images get too large to be practical
+const SVG_DOTS: u64 = 500_000;
+
+fn is_svg(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("svg"))
+}
+
+/// Write `img` as an SVG, with the most common color as the background and a rectangle for every
+/// horizontal run of pixels in another color
+fn write_svg<W: Write>(img: &RgbaImage, mut writer: W) -> io::Result<()> {
+    let (width, height) = img.dimensions();
+    let mut counts = HashMap::new();
+    for px in img.pixels() {
+        *counts.entry(*px).or_insert(0u64) += 1;
+    }
+    let background = counts
+        .into_iter()
+        .max_by_key(|&(_, count)| count)
+        .map(|(px, _)| px)
+        .unwrap_or(Rgba([0, 0, 0, 0]));
+
+    writeln!(
+        writer,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}" shape-rendering="crispEdges">"#
+    )?;
+    if background[3] != 0 {
+        writeln!(
+            writer,
+            r#"<rect width="{width}" height="{height}" {}/>"#,
+            svg_fill(background)
+        )?;
+    }
 
-        if !(hex_code.len() == 3 || hex_code.len() == 6) {
-            warn!("The length of the provided hex code should be equal to 3 or 6.");
-            return Rgb([255, 255, 255]);
+    writeln!(writer, "<g>")?;
+    for y in 0..height {
+        let mut x = 0;
+        while x < width {
+            let px = *img.get_pixel(x, y);
+            let mut run = 1;
+            while x + run < width && *img.get_pixel(x + run, y) == px {
+                run += 1;
+            }
+            if px != background && px[3] != 0 {
+                writeln!(
+                    writer,
+                    r#"<rect x="{x}" y="{y}" width="{run}" height="1" {}/>"#,
+                    svg_fill(px)
+                )?;
+            }
+            x += run;
         }
+    }
+    writeln!(writer, "</g>")?;
+    writeln!(writer, "</svg>")?;
+
+    writer.flush()
+}
+
+fn svg_fill(Rgba([r, g, b, a]): Rgba<u8>) -> String {
+    let fill = format!(r##"fill="#{r:02x}{g:02x}{b:02x}""##);
+    if a == 255 {
+        fill
+    } else {
+        format!(r#"{fill} fill-opacity="{:.3}""#, a as f64 / 255.0)
+    }
+}
 
-        // Convert shorthand hex code to normal hex code (https://en.wikipedia.org/wiki/Web_colors#Shorthand_hexadecimal_form)
-        if hex_code.len() == 3 {
-            let mut tmp = String::new();
-            for c in hex_code.chars() {
-                for _ in 0..2 {
-                    tmp.push(c);
+/// Where the image (Or the animation with `animation`) is saved, or stdout with `--output -`
+fn output_path(width: u32, height: u32, dots: u64, animation: bool, save: &Save) -> String {
+    match &save.output {
+        Some(path) if path == "-" => "stdout".to_string(),
+        Some(path) => {
+            let mut path = PathBuf::from(path);
+            if let Some(n) = save.index {
+                let mut name = path.file_stem().unwrap_or_default().to_os_string();
+                name.push(format!("-{n}"));
+                if let Some(extension) = path.extension() {
+                    name.push(".");
+                    name.push(extension);
                 }
+                path.set_file_name(name);
             }
+            if animation && AnimationFormat::from_path(&path).is_none() {
+                path.set_extension("gif");
+            }
+            path.display().to_string()
+        }
+        None => default_filename(width, height, dots, &FilenameOpts::new(save, animation)),
+    }
+}
+
+/// Everything changing the name of an image saved without an `--output`
+#[derive(Default)]
+struct FilenameOpts<'a> {
+    /// The time the image was made at, with `--timestamp`
+    timestamp: Option<NaiveDateTime>,
+
+    /// The number of the image, with `--repeat`
+    index: Option<u32>,
+
+    /// How much larger the image was made than it's saved (1 or 0 when it isn't)
+    supersample: u32,
+
+    /// The extension of the file (Like png)
+    extension: &'a str,
+
+    /// The directory the image is saved in, instead of the current one
+    dir: Option<&'a Path>,
+}
+
+impl<'a> FilenameOpts<'a> {
+    /// The name changes asked for in `save`, for an image (Or an animation with `animation`) made now
+    fn new(save: &'a Save, animation: bool) -> Self {
+        FilenameOpts {
+            timestamp: save.timestamp.then(|| Local::now().naive_local()),
+            index: save.index,
+            supersample: save.supersample,
+            extension: if animation {
+                "gif"
+            } else {
+                save.format.extensions_str()[0]
+            },
+            dir: save.output_dir.as_deref(),
+        }
+    }
+}
+
+/// Make the `--output-dir` (If given) so the image can be saved in it
+fn create_output_dir(save: &Save) {
+    if let Some(dir) = &save.output_dir {
+        fs::create_dir_all(dir).unwrap_or_else(|err| {
+            error!("Couldn't make the directory {}: {err}", dir.display());
+            process::exit(1);
+        });
+    }
+}
+
+/// The path an image is saved to when no output is given, like `1920x1080 - 100000.png`
+fn default_filename(width: u32, height: u32, dots: u64, opts: &FilenameOpts) -> String {
+    // Name supersampled images by the dots asked for, not the ones made at the larger size
+    let dots = dots / u64::from(opts.supersample.max(1)).pow(2);
+    let mut name = format!("{width}x{height} - {dots}");
+    if let Some(time) = opts.timestamp {
+        name += &format!(" - {}", time.format("%Y%m%dT%H%M%S"));
+    }
+    if let Some(n) = opts.index {
+        name += &format!("-{n}");
+    }
+    name += &format!(".{}", opts.extension);
+
+    match opts.dir {
+        Some(dir) => dir.join(name).display().to_string(),
+        None => name,
+    }
+}
+
+/// Whether the output should be written to stdout (With `--output -`)
+fn is_stdout(save: &Save) -> bool {
+    if save.output.as_deref() != Some("-") {
+        return false;
+    }
+
+    if save.wallpaper {
+        error!("Can't set the image as wallpaper when writing it to stdout");
+        process::exit(1);
+    }
+
+    if save.preview {
+        warn!("Can't preview the image when writing it to stdout, ignoring --preview");
+    }
+
+    if save.metadata {
+        warn!("Can't save the settings next to the image when writing it to stdout, ignoring --metadata");
+    }
+
+    true
+}
+
+fn check_overwrite(save_path: &Path, force: bool) {
+    if !force && save_path.exists() {
+        error!(
+            "{} already exists, use --force to overwrite it",
+            save_path.display()
+        );
+        process::exit(1);
+    }
+}
+
+/// The size of the primary screen in pixels, for wallpapers without a given size
+fn screen_size() -> (u32, u32) {
+    let displays = DisplayInfo::all().unwrap_or_else(|err| {
+        error!("Couldn't find the size of the screen, use --width and --height instead: {err}");
+        process::exit(1);
+    });
+    let display = displays
+        .iter()
+        .find(|display| display.is_primary)
+        .or_else(|| displays.first())
+        .unwrap_or_else(|| {
+            error!("Couldn't find a screen, use --width and --height instead");
+            process::exit(1);
+        });
 
-            hex_code = tmp;
+    let scale = |size: u32| (size as f32 * display.scale_factor).round() as u32;
+    let (width, height) = (scale(display.width), scale(display.height));
+    info!("Using the size of the screen: {width}x{height}");
+
+    (width, height)
+}
+
+fn set_wallpaper(save_path: &str, mode: Option<WallpaperMode>) {
+    info!("Setting image as wallpaper");
+    let path = fs::canonicalize(save_path).unwrap_or_else(|err| {
+        error!("Couldn't find the saved image {save_path}: {err}");
+        process::exit(1);
+    });
+    let path = path.to_str().unwrap_or_else(|| {
+        error!("The path {} isn't valid UTF-8", path.display());
+        process::exit(1);
+    });
+
+    wallpaper::set_from_path(path).unwrap_or_else(|err| {
+        error!("Couldn't set {path} as wallpaper: {err}");
+        process::exit(1);
+    });
+
+    if let Some(mode) = mode {
+        // The wallpaper is already set, so a desktop without modes isn't worth failing over
+        if let Err(err) = wallpaper::set_mode(mode.into()) {
+            warn!("Couldn't set the wallpaper mode to {mode:?}: {err}");
+        }
+    }
+}
+
+/// Encode `img` as a WebP image, lossy at `quality` if it's given and lossless otherwise
+fn encode_webp(img: &DynamicImage, quality: Option<u8>) -> Result<Vec<u8>, String> {
+    let rgba = img.to_rgba8();
+    let encoder = webp::Encoder::from_rgba(rgba.as_raw(), rgba.width(), rgba.height());
+    // The quality of lossless images is how hard it tries to make them smaller, like for cwebp
+    let encoded = match quality {
+        Some(quality) => encoder.encode_simple(false, f32::from(quality)),
+        None => encoder.encode_simple(true, 75.0),
+    };
+
+    encoded
+        .map(|webp| webp.to_vec())
+        .map_err(|err| format!("Couldn't encode the WebP image: {err:?}"))
+}
+
+/// Copy `img` to the clipboard, only warning when that fails as it's still saved
+fn copy_to_clipboard(img: &DynamicImage) {
+    info!("Copying image to the clipboard");
+    let image = ImageData {
+        width: img.width() as usize,
+        height: img.height() as usize,
+        bytes: img.to_rgba8().into_raw().into(),
+    };
+    let copied = Clipboard::new().and_then(|mut clipboard| clipboard.set_image(image));
+    if let Err(err) = copied {
+        warn!("Couldn't copy the image to the clipboard: {err}");
+    }
+}
+
+/// Open the saved image with the default program, only warning when that fails as it's already saved
+fn preview(save_path: &str) {
+    info!("Opening {save_path}");
+    if let Err(err) = open::that(save_path) {
+        warn!("Couldn't open {save_path}: {err}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::NaiveDate;
+
+    fn png() -> FilenameOpts<'static> {
+        FilenameOpts {
+            extension: "png",
+            ..FilenameOpts::default()
+        }
+    }
+
+    #[test]
+    fn plain_filename() {
+        assert_eq!(
+            default_filename(1920, 1080, 100000, &png()),
+            "1920x1080 - 100000.png"
+        );
+    }
+
+    #[test]
+    fn filename_with_timestamp() {
+        let time = NaiveDate::from_ymd_opt(2022, 6, 1)
+            .and_then(|date| date.and_hms_opt(13, 5, 9))
+            .unwrap();
+        let opts = FilenameOpts {
+            timestamp: Some(time),
+            ..png()
+        };
+        assert_eq!(
+            default_filename(100, 50, 1000, &opts),
+            "100x50 - 1000 - 20220601T130509.png"
+        );
+    }
+
+    #[test]
+    fn filename_in_output_dir() {
+        let opts = FilenameOpts {
+            dir: Some(Path::new("out")),
+            ..png()
+        };
+        let expected = Path::new("out").join("100x50 - 1000.png");
+        assert_eq!(
+            default_filename(100, 50, 1000, &opts),
+            expected.display().to_string()
+        );
+    }
+
+    #[test]
+    fn filename_with_format() {
+        let opts = FilenameOpts {
+            extension: "jpg",
+            ..png()
+        };
+        assert_eq!(default_filename(100, 50, 1000, &opts), "100x50 - 1000.jpg");
+    }
+
+    #[test]
+    fn filename_with_repeat() {
+        let opts = FilenameOpts {
+            index: Some(2),
+            ..png()
+        };
+        assert_eq!(
+            default_filename(100, 50, 1000, &opts),
+            "100x50 - 1000-2.png"
+        );
+    }
+
+    #[test]
+    fn filename_of_supersampled_image() {
+        let opts = FilenameOpts {
+            supersample: 2,
+            ..png()
+        };
+        assert_eq!(default_filename(100, 50, 4000, &opts), "100x50 - 1000.png");
+    }
+
+    #[test]
+    fn aspect_ratio() {
+        assert_eq!(parse_aspect("16:9"), Ok([16.0, 9.0]));
+        assert_eq!(parse_aspect(" 2.35 : 1 "), Ok([2.35, 1.0]));
+    }
+
+    #[test]
+    fn invalid_aspect_ratio() {
+        for aspect in [
+            "0:1", "16:0", "16:", ":9", "-16:9", "16:-9", "a:b", "16x9", "inf:1", "",
+        ] {
+            assert!(parse_aspect(aspect).is_err(), "{aspect} was accepted");
         }
+    }
+
+    #[test]
+    fn size_from_aspect_ratio() {
+        assert_eq!(aspect_size(Some(1920), None, [16.0, 9.0]), Ok((1920, 1080)));
+        assert_eq!(aspect_size(None, Some(1080), [16.0, 9.0]), Ok((1920, 1080)));
+        // 1000 / 16 * 9 is 562.5, which rounds up
+        assert_eq!(aspect_size(Some(1000), None, [16.0, 9.0]), Ok((1000, 563)));
+        assert_eq!(aspect_size(None, Some(100), [1.0, 3.0]), Ok((33, 100)));
+    }
+
+    #[test]
+    fn size_matching_aspect_ratio() {
+        // 1366x768 is called 16:9, though it's off by a fraction of a pixel
+        assert_eq!(
+            aspect_size(Some(1366), Some(768), [16.0, 9.0]),
+            Ok((1366, 768))
+        );
+        assert!(aspect_size(Some(1000), Some(1000), [16.0, 9.0]).is_err());
+        assert!(aspect_size(None, None, [16.0, 9.0]).is_err());
+        assert!(aspect_size(Some(1), None, [3.0, 1.0]).is_err());
+    }
 
-        match (0..hex_code.len())
-            .step_by(2)
-            .map(|i| u8::from_str_radix(&hex_code[i..i + 2], 16))
-            .collect::<Result<Vec<u8>, ParseIntError>>()
-        {
-            Ok(vec) => {
-                Rgb([vec[0], vec[1], vec[2]])
+    #[test]
+    fn output_from_environment() {
+        std::env::set_var("SIERPINSKI_OUTPUT", "from-env.png");
+        let args = Cli::try_parse_from([
+            "sierpinski-triangle",
+            "generate",
+            "-w",
+            "10",
+            "-h",
+            "10",
+            "-d",
+            "10",
+        ]);
+        std::env::remove_var("SIERPINSKI_OUTPUT");
+
+        match args.unwrap().command {
+            Commands::Generate { save, .. } => {
+                assert_eq!(save.output.as_deref(), Some("from-env.png"))
             }
-            Err(error) => {
-                match error.kind() {
-                    IntErrorKind::InvalidDigit => {
-                        warn!("There was an illegal character in the color code, using white.")
-                    }
-                    _ => warn!("An unknown error occurred while parsing the color, using white."),
-                }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn sixteen_bit_grayscale_image() {
+        let path = std::env::temp_dir().join("sierpinski-triangle-gray16.png");
+        ImageBuffer::from_pixel(4, 4, image::Luma([0x8080u16]))
+            .save(&path)
+            .unwrap();
+        let im = read_rgb_image(path.to_str().unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(*im.get_pixel(0, 0), Rgb([128, 128, 128]));
+        let backdrop = image_backdrop(&im, -50, true);
+        assert_eq!(*backdrop.get_pixel(0, 0), Rgb([78, 78, 78]));
+    }
 
-                Rgb([255, 255, 255])
+    /// The long options of `command` and its subcommands that completions should offer
+    fn longs(command: &clap::Command) -> Vec<String> {
+        let own = command
+            .get_arguments()
+            .filter(|arg| !arg.is_positional() && !arg.is_hide_set())
+            .filter_map(|arg| arg.get_long().map(str::to_string));
+        let subcommands = command
+            .get_subcommands()
+            .filter(|sub| !sub.is_hide_set())
+            .flat_map(longs);
+        own.chain(subcommands).collect()
+    }
+
+    #[test]
+    fn every_long_flag_is_completed() {
+        let longs = longs(&Cli::command());
+        assert!(longs.iter().any(|long| long == "width"));
+
+        for &shell in clap_complete::Shell::value_variants() {
+            let mut script = Vec::new();
+            clap_complete::generate(
+                shell,
+                &mut Cli::command(),
+                "sierpinski-triangle",
+                &mut script,
+            );
+            let script = String::from_utf8(script).unwrap();
+            // Split on anything but the characters of options, so --output isn't found in --output-dir
+            let words: Vec<_> = script
+                .split(|c: char| !c.is_ascii_alphanumeric() && c != '-')
+                .filter(|word| !word.is_empty())
+                .collect();
+            for long in &longs {
+                // Fish writes the long options as `-l width`
+                let completed = words.contains(&format!("--{long}").as_str())
+                    || words.windows(2).any(|pair| pair == ["-l", long.as_str()]);
+                assert!(
+                    completed,
+                    "--{long} is missing from the {shell} completions"
+                );
             }
         }
-    } else {
-        info!("No hex color provided, using white.");
-        Rgb([255, 255, 255])
     }
 }
@@ -0,0 +1,42 @@
+//! Masks limiting the pixels the dots of the chaos game can land on
+
+use image::GrayImage;
+
+/// Brightest gray of the pixels of a mask image the dots can't land on
+const THRESHOLD: u8 = 127;
+
+/// The pixels of an image the dots may land on, with the jumps onto any other pixel rolled again
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mask {
+    width: u32,
+    height: u32,
+    allowed: Vec<bool>,
+}
+
+impl Mask {
+    /// A mask allowing the light pixels of `image` (Over half brightness) and not the dark ones
+    pub fn from_image(image: &GrayImage) -> Self {
+        Mask {
+            width: image.width(),
+            height: image.height(),
+            allowed: image.pixels().map(|px| px[0] > THRESHOLD).collect(),
+        }
+    }
+
+    /// The width and height of the mask (In pixels)
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Whether a dot may land on `x`,`y`, which it never may outside the mask
+    pub fn allows(&self, x: u32, y: u32) -> bool {
+        x < self.width
+            && y < self.height
+            && self.allowed[y as usize * self.width as usize + x as usize]
+    }
+
+    /// The number of pixels the dots may land on
+    pub fn count(&self) -> usize {
+        self.allowed.iter().filter(|&&allowed| allowed).count()
+    }
+}
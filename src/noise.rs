@@ -0,0 +1,55 @@
+//! Smooth random noise for textured backgrounds
+
+/// Number of layers of finer and finer noise added together
+const OCTAVES: u64 = 4;
+
+/// Number of cells of the coarsest noise across the longest side of the image
+const CELLS: f64 = 8.0;
+
+/// Value noise at `x`,`y` on a `width`x`height` image, between 0 and 1 and always the same for the
+/// same `seed`
+///
+/// Random values on a grid of [`CELLS`] cells across the image are blended smoothly, with every
+/// octave adding a grid twice as fine at half the strength.
+pub fn value_noise(x: u32, y: u32, width: u32, height: u32, seed: u64) -> f64 {
+    let mut cell = width.max(height) as f64 / CELLS;
+    let (mut sum, mut total, mut strength) = (0.0, 0.0, 1.0);
+    for octave in 0..OCTAVES {
+        sum += strength * lattice(x as f64 / cell, y as f64 / cell, seed.wrapping_add(octave));
+        total += strength;
+        strength /= 2.0;
+        cell /= 2.0;
+    }
+
+    sum / total
+}
+
+/// The random values at the corners of the grid cell around `x`,`y`, blended by how close it is
+/// to each of them
+fn lattice(x: f64, y: f64, seed: u64) -> f64 {
+    let (left, top) = (x.floor(), y.floor());
+    let (tx, ty) = (smooth(x - left), smooth(y - top));
+    let (left, top) = (left as i64, top as i64);
+    let corner = |dx, dy| random(left + dx, top + dy, seed);
+
+    let upper = corner(0, 0) + (corner(1, 0) - corner(0, 0)) * tx;
+    let lower = corner(0, 1) + (corner(1, 1) - corner(0, 1)) * tx;
+    upper + (lower - upper) * ty
+}
+
+/// Ease `t` from 0 to 1, so the blend has no visible edges at the grid lines
+fn smooth(t: f64) -> f64 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// A random value from 0 to 1 for the grid point `x`,`y`, hashed with SplitMix64
+fn random(x: i64, y: i64, seed: u64) -> f64 {
+    let mut z = seed
+        ^ (x as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        ^ (y as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
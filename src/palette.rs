@@ -0,0 +1,133 @@
+//! Reading the colors of a palette file, as a GIMP palette or a list of colors
+
+use image::Rgba;
+use log::warn;
+use sierpinski_triangle::get_color;
+
+use std::fs;
+
+/// First line of GIMP palettes
+const GIMP_HEADER: &str = "GIMP Palette";
+
+/// Read the colors of the palette at `path`, skipping the lines that aren't colors with a warning
+///
+/// Files starting with `GIMP Palette` are read as GIMP palettes, with the red, green and blue of a
+/// color at the start of every line after the header. Anything else is read as a color in hex
+/// format or a CSS color name on every line. Empty lines are skipped in both.
+pub fn read(path: &str) -> Result<Vec<Rgba<u8>>, String> {
+    let text = fs::read_to_string(path).map_err(|err| format!("Couldn't read {path}: {err}"))?;
+    let mut lines = text.lines().enumerate().peekable();
+    let gimp = lines
+        .peek()
+        .is_some_and(|(_, line)| line.trim() == GIMP_HEADER);
+    if gimp {
+        lines.next();
+    }
+
+    let mut colors = Vec::new();
+    for (n, line) in lines {
+        let line = line.trim();
+        let color = if gimp {
+            // Names, column counts and comments come before the colors
+            if line.is_empty()
+                || line.starts_with('#')
+                || line.starts_with("Name:")
+                || line.starts_with("Columns:")
+            {
+                continue;
+            }
+            gimp_color(line)
+        } else {
+            if line.is_empty() {
+                continue;
+            }
+            get_color(line).map_err(|err| err.to_string())
+        };
+
+        match color {
+            Ok(color) => colors.push(color),
+            Err(err) => warn!("Skipping line {} of {path}: {err}", n + 1),
+        }
+    }
+
+    if colors.is_empty() {
+        return Err(format!("{path} has no colors"));
+    }
+
+    Ok(colors)
+}
+
+/// The color of a line of a GIMP palette, like `255 128 0 Orange`
+fn gimp_color(line: &str) -> Result<Rgba<u8>, String> {
+    let mut channels = line.split_whitespace().map(str::parse::<u8>);
+    match (channels.next(), channels.next(), channels.next()) {
+        (Some(Ok(r)), Some(Ok(g)), Some(Ok(b))) => Ok(Rgba([r, g, b, 255])),
+        _ => Err(format!(
+            "\"{line}\" doesn't start with a red, green and blue from 0 to 255"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    /// Write `text` to a palette file in the temporary directory, named after the test using it
+    fn palette_file(name: &str, text: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("sierpinski-triangle-{name}.txt"));
+        fs::write(&path, text).unwrap();
+        path
+    }
+
+    fn read_file(name: &str, text: &str) -> Result<Vec<Rgba<u8>>, String> {
+        let path = palette_file(name, text);
+        let colors = read(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+        colors
+    }
+
+    #[test]
+    fn list_of_colors() {
+        let colors = read_file("list", "#ff8000\n\n  1e90ff  \nred\n\n#fff\n");
+        assert_eq!(
+            colors,
+            Ok(vec![
+                Rgba([255, 128, 0, 255]),
+                Rgba([30, 144, 255, 255]),
+                Rgba([255, 0, 0, 255]),
+                Rgba([255, 255, 255, 255]),
+            ])
+        );
+    }
+
+    #[test]
+    fn gimp_palette() {
+        let text = "GIMP Palette\nName: Sunset\nColumns: 2\n# Warm colors\n\n255 128   0\tOrange\n  0   0 255 Blue\n";
+        assert_eq!(
+            read_file("gimp", text),
+            Ok(vec![Rgba([255, 128, 0, 255]), Rgba([0, 0, 255, 255])])
+        );
+    }
+
+    #[test]
+    fn bad_lines_are_skipped() {
+        assert_eq!(
+            read_file("bad-list", "#ff0000\nnot a color\n#00ff00\n"),
+            Ok(vec![Rgba([255, 0, 0, 255]), Rgba([0, 255, 0, 255])])
+        );
+        assert_eq!(
+            read_file("bad-gimp", "GIMP Palette\n255 0\n300 0 0\n0 0 255\n"),
+            Ok(vec![Rgba([0, 0, 255, 255])])
+        );
+    }
+
+    #[test]
+    fn no_colors() {
+        let path = palette_file("empty", "GIMP Palette\n# Nothing yet\n\nnot a color\n");
+        let path = path.to_str().unwrap();
+        assert_eq!(read(path), Err(format!("{path} has no colors")));
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,52 @@
+//! Counting how often the dots of the chaos game hit each pixel, for --stats
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// How many times each pixel of an image was hit by the dots, counted as they're placed
+///
+/// The counts are atomic, so the walks can count into the same ones from any number of threads.
+#[derive(Debug)]
+pub struct HitCounts {
+    width: u32,
+    counts: Vec<AtomicU64>,
+}
+
+impl HitCounts {
+    /// Counts for a `width`x`height` image that hasn't been hit yet
+    pub fn new(width: u32, height: u32) -> Self {
+        HitCounts {
+            width,
+            counts: (0..width as usize * height as usize)
+                .map(|_| AtomicU64::new(0))
+                .collect(),
+        }
+    }
+
+    /// Count a dot landing on `x`,`y`
+    pub(crate) fn hit(&self, x: u32, y: u32) {
+        let index = y as usize * self.width as usize + x as usize;
+        self.counts[index].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The number of pixels hit at least once
+    pub fn hit_pixels(&self) -> usize {
+        self.counts
+            .iter()
+            .filter(|count| count.load(Ordering::Relaxed) > 0)
+            .count()
+    }
+
+    /// The most times any pixel was hit
+    pub fn busiest(&self) -> u64 {
+        self.counts
+            .iter()
+            .map(|count| count.load(Ordering::Relaxed))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The number of pixels counted
+    pub fn pixels(&self) -> usize {
+        self.counts.len()
+    }
+}
@@ -0,0 +1,163 @@
+//! The chaos game in three dimensions, giving a Sierpiński tetrahedron seen from slightly above
+
+use image::{ImageBuffer, Pixel};
+use log::info;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use std::time::Instant;
+
+use crate::{
+    choose, get_seed, inset, log_timing, mark, progress_bar, progress_step, scale_channel, stamp,
+    Options,
+};
+
+/// How far the tetrahedron is tilted towards the viewer (In degrees), so the base isn't seen edge on
+const TILT: f64 = 20.0;
+
+/// Brightness of the farthest dots when shading by depth (The closest ones keep their full color)
+const FAR_BRIGHTNESS: f64 = 0.3;
+
+/// Play the chaos game between the four vertices of a regular tetrahedron turned `rotate` degrees
+/// around its vertical axis, and draw the dots projected straight onto `image`
+///
+/// With `shade`, dots get darker the farther back they are. This always runs on a single thread
+/// and ignores the vertex and start settings of `options`.
+pub fn make_tetrahedron<P, F>(
+    image: ImageBuffer<P, Vec<P::Subpixel>>,
+    dots: u64,
+    options: &Options,
+    rotate: f64,
+    shade: bool,
+    color: F,
+) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel,
+    F: Fn(u32, u32, u64) -> P,
+{
+    let (width, height) = image.dimensions();
+    info!("Creating a Sierpiński tetrahedron with {dots} points on a {width}x{height} image");
+
+    let vertices = get_vertices(rotate);
+    let screen = Screen::new(&vertices, width, height, options.margin);
+    let color = |x, y, i, brightness: f64| {
+        let px = color(x, y, i);
+        if shade {
+            px.map_without_alpha(|c| scale_channel(c, brightness))
+        } else {
+            px
+        }
+    };
+
+    info!("Creating image");
+    let mut img = image;
+
+    if options.corners {
+        info!("Placing corners");
+        for &vertex in &vertices {
+            let (x, y, brightness) = screen.project(vertex);
+            mark(&mut img, x, y, options, |x, y| color(x, y, 0, brightness));
+        }
+    }
+
+    let seed = get_seed(options);
+    info!("Placing dots (Seed: {seed})");
+    let bar = progress_bar(dots, options);
+    let tick = progress_step(dots);
+    let start_time = Instant::now();
+    let mut rng = StdRng::seed_from_u64(seed);
+    // Start on the apex, which is on the fractal already
+    let mut last = vertices[0];
+    let mut prev = None;
+    let mut placed = 0;
+    let distribution = options.distribution();
+    for step in 0..options.skip.saturating_add(dots) {
+        let n = choose(
+            &mut rng,
+            prev,
+            vertices.len(),
+            options.restrict,
+            distribution.as_ref(),
+        );
+        prev = Some(n);
+        // The first dots aren't drawn, as they haven't reached the fractal yet
+        if step >= options.skip {
+            let i = step - options.skip + 1;
+            let (x, y, brightness) = screen.project(last);
+            stamp(&mut img, x, y, i, options.dot_size, |x, y, i| {
+                color(x, y, i, brightness)
+            });
+            placed = i;
+            if i.is_multiple_of(tick) {
+                bar.inc(tick);
+                if options.stopped() {
+                    break;
+                }
+            }
+        }
+        for (axis, target) in last.iter_mut().zip(vertices[n]) {
+            *axis += (target - *axis) * options.ratio;
+        }
+    }
+    bar.finish();
+    log_timing(placed, dots, options, start_time.elapsed());
+
+    img
+}
+
+/// The vertices of a regular tetrahedron around the origin with its apex at the top, turned and tilted
+///
+/// The axes point right, up and towards the viewer.
+fn get_vertices(rotate: f64) -> [[f64; 3]; 4] {
+    let radius = 8f64.sqrt() / 3.0;
+    let base = |k: f64| {
+        let angle = (rotate + 120.0 * k).to_radians();
+        [radius * angle.sin(), -1.0 / 3.0, radius * angle.cos()]
+    };
+
+    let (sin, cos) = TILT.to_radians().sin_cos();
+    [[0.0, 1.0, 0.0], base(0.0), base(1.0), base(2.0)]
+        .map(|[x, y, z]| [x, y * cos - z * sin, y * sin + z * cos])
+}
+
+/// Maps points onto the image, fitting the tetrahedron inside the margin
+struct Screen {
+    center: [f64; 2],
+    scale: f64,
+    depth: [f64; 2],
+    size: [u32; 2],
+}
+
+impl Screen {
+    fn new(vertices: &[[f64; 3]], width: u32, height: u32, margin: f64) -> Self {
+        let bounds = |axis: usize| {
+            vertices.iter().fold([f64::MAX, f64::MIN], |[min, max], v| {
+                [min.min(v[axis]), max.max(v[axis])]
+            })
+        };
+        let (x, y, z) = (bounds(0), bounds(1), bounds(2));
+        let inner = |size: u32| (size - 2 * inset(size, margin)) as f64;
+
+        Screen {
+            center: [(x[0] + x[1]) / 2.0, (y[0] + y[1]) / 2.0],
+            scale: (inner(width) / (x[1] - x[0])).min(inner(height) / (y[1] - y[0])),
+            depth: z,
+            size: [width, height],
+        }
+    }
+
+    /// The pixel a point lands on, and its brightness from its depth
+    fn project(&self, [x, y, z]: [f64; 3]) -> (u32, u32, f64) {
+        let [width, height] = self.size;
+        let px = width as f64 / 2.0 + (x - self.center[0]) * self.scale;
+        let py = height as f64 / 2.0 - (y - self.center[1]) * self.scale;
+        let [near, far] = [self.depth[1], self.depth[0]];
+        let brightness = FAR_BRIGHTNESS + (1.0 - FAR_BRIGHTNESS) * (z - far) / (near - far);
+
+        (
+            (px.max(0.0) as u32).min(width - 1),
+            (py.max(0.0) as u32).min(height - 1),
+            brightness.clamp(FAR_BRIGHTNESS, 1.0),
+        )
+    }
+}
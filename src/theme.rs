@@ -0,0 +1,199 @@
+//! Finding the color of the theme of the desktop or the terminal, for `auto` colors
+
+use image::{Pixel, Rgb, Rgba};
+
+use std::process::Command;
+
+/// How long the terminal gets to answer the query for its color (In milliseconds)
+#[cfg(unix)]
+const TERMINAL_TIMEOUT: i32 = 200;
+
+/// The accent color of the desktop, or the text color of the terminal when there's none
+pub fn theme_color() -> Option<Rgba<u8>> {
+    accent_color().or_else(terminal_color)
+}
+
+/// The output of `program` run with `args`, if it ran and succeeded
+fn output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// The accent color picked in the settings of macOS
+#[cfg(target_os = "macos")]
+fn accent_color() -> Option<Rgba<u8>> {
+    // Blue is the default, which isn't saved
+    let accent = output("defaults", &["read", "-g", "AppleAccentColor"]).unwrap_or_default();
+    let name = match accent.as_str() {
+        "-1" => "gray",
+        "0" => "#ff5257",
+        "1" => "#f7821b",
+        "2" => "#ffc600",
+        "3" => "#62ba46",
+        "5" => "#a550a7",
+        "6" => "#f74f9e",
+        _ => "#007aff",
+    };
+    crate::get_color(name).ok()
+}
+
+/// The accent color picked in the settings of Windows
+#[cfg(windows)]
+fn accent_color() -> Option<Rgba<u8>> {
+    let query = output(
+        "reg",
+        &[
+            "query",
+            r"HKCU\Software\Microsoft\Windows\DWM",
+            "/v",
+            "AccentColor",
+        ],
+    )?;
+    // The value is saved as 0xAABBGGRR
+    let value = query.split_whitespace().last()?.strip_prefix("0x")?;
+    let [_, b, g, r] = u32::from_str_radix(value, 16).ok()?.to_be_bytes();
+    Some(Rgb([r, g, b]).to_rgba())
+}
+
+/// The accent color picked in the settings of GNOME (From version 47, which has a few named ones)
+#[cfg(not(any(target_os = "macos", windows)))]
+fn accent_color() -> Option<Rgba<u8>> {
+    let accent = output(
+        "gsettings",
+        &["get", "org.gnome.desktop.interface", "accent-color"],
+    )?;
+    let name = match accent.trim_matches('\'') {
+        "blue" => "#3584e4",
+        "teal" => "#2190a4",
+        "green" => "#3a944a",
+        "yellow" => "#c88800",
+        "orange" => "#ed5b00",
+        "red" => "#e62d42",
+        "pink" => "#d56199",
+        "purple" => "#9141ac",
+        "slate" => "#6f8396",
+        _ => return None,
+    };
+    crate::get_color(name).ok()
+}
+
+/// The text color of the terminal, asked for with an OSC 10 escape sequence
+///
+/// The terminal answers on its input, which is read without echoing it for a moment. Terminals
+/// that don't answer leave nothing to read, so this gives up after [`TERMINAL_TIMEOUT`].
+#[cfg(unix)]
+fn terminal_color() -> Option<Rgba<u8>> {
+    use std::fs::OpenOptions;
+    use std::io::{Read, Write};
+    use std::os::unix::io::AsRawFd;
+
+    let mut tty = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .ok()?;
+    let fd = tty.as_raw_fd();
+
+    let mut saved = unsafe { std::mem::zeroed::<libc::termios>() };
+    if unsafe { libc::tcgetattr(fd, &mut saved) } != 0 {
+        return None;
+    }
+    let mut raw = saved;
+    raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &raw) };
+
+    let mut reply = Vec::new();
+    if tty
+        .write_all(b"\x1b]10;?\x07")
+        .and_then(|_| tty.flush())
+        .is_ok()
+    {
+        let mut poll = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let mut byte = [0];
+        // The reply ends with a bell or an ST (Escape and a backslash)
+        while !reply.ends_with(b"\x07") && !reply.ends_with(b"\x1b\\") {
+            if unsafe { libc::poll(&mut poll, 1, TERMINAL_TIMEOUT) } <= 0
+                || tty.read(&mut byte).unwrap_or(0) == 0
+            {
+                break;
+            }
+            reply.push(byte[0]);
+        }
+    }
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &saved) };
+
+    parse_reply(&String::from_utf8_lossy(&reply))
+}
+
+#[cfg(not(unix))]
+fn terminal_color() -> Option<Rgba<u8>> {
+    None
+}
+
+/// Read a color from the `rgb:RRRR/GGGG/BBBB` the terminal answers with, where each channel has
+/// 1 to 4 hex digits
+fn parse_reply(reply: &str) -> Option<Rgba<u8>> {
+    let channels = reply
+        .split_once("rgb:")?
+        .1
+        .trim_end_matches(['\x07', '\x1b', '\\']);
+    let channels: Vec<u8> = channels
+        .split('/')
+        .map(|channel| {
+            let max = 16u64.checked_pow(channel.len() as u32)? - 1;
+            let value = u64::from_str_radix(channel, 16).ok()?;
+            Some((value * 255 / max) as u8)
+        })
+        .collect::<Option<_>>()?;
+
+    match channels[..] {
+        [r, g, b] => Some(Rgb([r, g, b]).to_rgba()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn four_digit_reply() {
+        assert_eq!(
+            parse_reply("\x1b]10;rgb:ffff/8080/0000\x07"),
+            Some(Rgba([255, 128, 0, 255]))
+        );
+        assert_eq!(
+            parse_reply("\x1b]10;rgb:1e1e/9090/ffff\x1b\\"),
+            Some(Rgba([30, 144, 255, 255]))
+        );
+    }
+
+    #[test]
+    fn two_digit_reply() {
+        assert_eq!(
+            parse_reply("\x1b]10;rgb:ff/80/00\x07"),
+            Some(Rgba([255, 128, 0, 255]))
+        );
+        assert_eq!(
+            parse_reply("\x1b]10;rgb:1e/90/ff\x1b\\"),
+            Some(Rgba([30, 144, 255, 255]))
+        );
+    }
+
+    #[test]
+    fn malformed_reply() {
+        assert_eq!(parse_reply(""), None);
+        assert_eq!(parse_reply("\x1b]10;#ff8000\x07"), None);
+        assert_eq!(parse_reply("\x1b]10;rgb:ff/80\x07"), None);
+        assert_eq!(parse_reply("\x1b]10;rgb:ff/80/00/00\x07"), None);
+        assert_eq!(parse_reply("\x1b]10;rgb:gg/80/00\x07"), None);
+        assert_eq!(parse_reply("\x1b]10;rgb:ff//00\x07"), None);
+    }
+}